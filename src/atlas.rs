@@ -0,0 +1,286 @@
+use crate::{
+    error::ParseAnimationError, mesh, options::AtlasOptions, sprite_sheet, trim::CellRect,
+};
+use amethyst::renderer::sprite::{SpriteList, Sprites};
+use image::RgbaImage;
+
+// 全てのセルマップのセルを切り出して、上限サイズのページに詰め直す
+// ページの並びがアニメーションの map_id、ページ内の並びがセルIDになる
+// 詰め方はセルの範囲のサイズだけで決まるので、何度計算しても同じ配置になる
+pub(crate) struct AtlasLayout {
+    pub(crate) pages: Vec<AtlasPage>,
+}
+
+pub(crate) struct AtlasPage {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) cells: Vec<PlacedCell>,
+}
+
+// 元の (全体のセルマップID, セルID) とページ内の配置先
+#[derive(Debug, PartialEq)]
+pub(crate) struct PlacedCell {
+    pub(crate) map_id: usize,
+    pub(crate) cell_id: usize,
+    pub(crate) x: u32,
+    pub(crate) y: u32,
+}
+
+// ページ内の一段
+struct Shelf {
+    y: u32,
+    height: u32,
+    x: u32,
+}
+
+struct PageSpace {
+    shelves: Vec<Shelf>,
+    bottom: u32,
+}
+
+impl PageSpace {
+    // 空いている段か新しい段に置けたらその位置を返す
+    fn insert(&mut self, width: u32, height: u32, options: &AtlasOptions) -> Option<(u32, u32)> {
+        if let Some(shelf) = self
+            .shelves
+            .iter_mut()
+            .find(|shelf| height <= shelf.height && shelf.x + width <= options.max_size)
+        {
+            let position = (shelf.x, shelf.y);
+            shelf.x += width + options.padding;
+            return Some(position);
+        }
+        if self.bottom + height > options.max_size {
+            return None;
+        }
+        let y = self.bottom;
+        self.shelves.push(Shelf {
+            y,
+            height,
+            x: width + options.padding,
+        });
+        self.bottom += height + options.padding;
+        Some((0, y))
+    }
+}
+
+// rects は [全体のセルマップID][セルID] のテクスチャ上の範囲
+pub(crate) fn layout(
+    project: &sprite_studio::SpriteStudioData,
+    rects: &[Vec<CellRect>],
+    options: &AtlasOptions,
+) -> Result<AtlasLayout, ParseAnimationError> {
+    let mut cells = vec![];
+    for (map_id, cell_map) in project.cell_maps().enumerate() {
+        for (cell_id, cell) in cell_map.cells().enumerate() {
+            let CellRect { width, height, .. } = rects[map_id][cell_id];
+            if width > options.max_size || height > options.max_size {
+                return Err(ParseAnimationError::AtlasCellTooLarge {
+                    map: cell_map.file_name().into(),
+                    name: cell.name().into(),
+                    width,
+                    height,
+                    max_size: options.max_size,
+                });
+            }
+            cells.push((map_id, cell_id, width, height));
+        }
+    }
+    pack_cells(cells, options)
+}
+
+// cells は (全体のセルマップID, セルID, 幅, 高さ)
+fn pack_cells(
+    mut cells: Vec<(usize, usize, u32, u32)>,
+    options: &AtlasOptions,
+) -> Result<AtlasLayout, ParseAnimationError> {
+    // 背の高いセルから段に並べると隙間が少ない
+    // 同じサイズはIDの順にして、渡された順番に関係なく同じ配置にする
+    cells.sort_by(|a, b| (b.3, b.2, a.0, a.1).cmp(&(a.3, a.2, b.0, b.1)));
+
+    let mut spaces: Vec<PageSpace> = vec![];
+    let mut pages: Vec<AtlasPage> = vec![];
+    for (map_id, cell_id, width, height) in cells {
+        let placed = spaces
+            .iter_mut()
+            .enumerate()
+            .find_map(|(page, space)| Some((page, space.insert(width, height, options)?)));
+        let (page, (x, y)) = match placed {
+            Some(placed) => placed,
+            None if pages.len() < options.max_pages => {
+                let mut space = PageSpace {
+                    shelves: vec![],
+                    bottom: 0,
+                };
+                let position = space.insert(width, height, options).unwrap();
+                spaces.push(space);
+                pages.push(AtlasPage {
+                    width: 0,
+                    height: 0,
+                    cells: vec![],
+                });
+                (pages.len() - 1, position)
+            }
+            None => {
+                return Err(ParseAnimationError::AtlasPageOverflow {
+                    max_pages: options.max_pages,
+                })
+            }
+        };
+
+        let page = &mut pages[page];
+        page.width = page.width.max(x + width);
+        page.height = page.height.max(y + height);
+        page.cells.push(PlacedCell {
+            map_id,
+            cell_id,
+            x,
+            y,
+        });
+    }
+
+    Ok(AtlasLayout { pages })
+}
+
+pub(crate) fn make_sprite_sheet(
+    project: &sprite_studio::SpriteStudioData,
+    rects: &[Vec<CellRect>],
+    page: &AtlasPage,
+) -> Sprites {
+    let cell_maps = project.cell_maps().collect::<Vec<_>>();
+    let sprites = page
+        .cells
+        .iter()
+        .map(|placed| {
+            let cell = cell_maps[placed.map_id]
+                .cells()
+                .nth(placed.cell_id)
+                .unwrap();
+            let rect = &rects[placed.map_id][placed.cell_id];
+            sprite_sheet::make_sprite_position(cell, rect, (placed.x, placed.y))
+        })
+        .collect();
+
+    Sprites::List(SpriteList {
+        texture_width: page.width,
+        texture_height: page.height,
+        sprites,
+    })
+}
+
+// メッシュの頂点はセルの左上が原点なので、配置が変わってもそのまま使える
+pub(crate) fn make_mesh_sheet(
+    project: &sprite_studio::SpriteStudioData,
+    page: &AtlasPage,
+) -> Option<mesh::MeshSheet> {
+    let cell_maps = project.cell_maps().collect::<Vec<_>>();
+    let meshes = page
+        .cells
+        .iter()
+        .map(|placed| {
+            let cell = cell_maps[placed.map_id]
+                .cells()
+                .nth(placed.cell_id)
+                .unwrap();
+            mesh::make_mesh_topology(cell)
+        })
+        .collect::<Vec<_>>();
+
+    if meshes.iter().any(Option::is_some) {
+        Some(mesh::MeshSheet { meshes })
+    } else {
+        None
+    }
+}
+
+pub(crate) fn make_page_image(
+    images: &[RgbaImage],
+    rects: &[Vec<CellRect>],
+    page: &AtlasPage,
+) -> RgbaImage {
+    let mut page_image = RgbaImage::new(page.width, page.height);
+    for placed in &page.cells {
+        let rect = &rects[placed.map_id][placed.cell_id];
+        let source = image::imageops::crop_imm(
+            &images[placed.map_id],
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
+        );
+        image::imageops::replace(&mut page_image, &source.to_image(), placed.x, placed.y);
+    }
+    page_image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(max_size: u32, max_pages: usize, padding: u32) -> AtlasOptions {
+        AtlasOptions {
+            max_size,
+            max_pages,
+            padding,
+        }
+    }
+
+    fn positions(layout: &AtlasLayout) -> Vec<Vec<(usize, usize, u32, u32)>> {
+        layout
+            .pages
+            .iter()
+            .map(|page| {
+                page.cells
+                    .iter()
+                    .map(|cell| (cell.map_id, cell.cell_id, cell.x, cell.y))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn insert_leaves_padding_between_cells() {
+        let options = options(32, 1, 2);
+        let mut space = PageSpace {
+            shelves: vec![],
+            bottom: 0,
+        };
+        assert_eq!(space.insert(10, 10, &options), Some((0, 0)));
+        assert_eq!(space.insert(10, 8, &options), Some((12, 0)));
+        // 段に入らない幅は次の段へ
+        assert_eq!(space.insert(12, 10, &options), Some((0, 12)));
+        // 残りの高さに入らない
+        assert_eq!(space.insert(30, 12, &options), None);
+    }
+
+    #[test]
+    fn placement_does_not_depend_on_input_order() {
+        let options = options(64, 2, 1);
+        let cells = vec![(0, 0, 8, 8), (0, 1, 16, 4), (1, 0, 8, 8), (1, 1, 4, 16)];
+        let layout = pack_cells(cells.clone(), &options).unwrap();
+        let reversed = pack_cells(cells.into_iter().rev().collect(), &options).unwrap();
+        assert_eq!(positions(&layout), positions(&reversed));
+        assert_eq!(
+            positions(&layout),
+            vec![vec![
+                (1, 1, 0, 0),
+                (0, 0, 5, 0),
+                (1, 0, 14, 0),
+                (0, 1, 23, 0)
+            ]]
+        );
+        assert_eq!((layout.pages[0].width, layout.pages[0].height), (39, 16));
+    }
+
+    #[test]
+    fn overflow_when_pages_run_out() {
+        let cells = vec![(0, 0, 10, 10), (0, 1, 10, 10), (0, 2, 10, 10)];
+        let layout = pack_cells(cells.clone(), &options(16, 3, 1)).unwrap();
+        assert_eq!(layout.pages.len(), 3);
+
+        match pack_cells(cells, &options(16, 2, 1)) {
+            Err(ParseAnimationError::AtlasPageOverflow { max_pages }) => assert_eq!(max_pages, 2),
+            _ => panic!("expected AtlasPageOverflow"),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{
+    atlas::{self, AtlasLayout},
+    error::ParseAnimationError,
+    options::ConvertOptions,
+    trim::{self, CellRect, TrimSummary},
+};
+use image::RgbaImage;
+
+// 出力するセルマップは project.cell_maps() の順番を全体のIDとする
+// 画像・シートのファイル名とアニメーションの map_id は必ずこのIDを使う
+pub(crate) struct CellMapTable {
+    maps: Vec<CellMapEntry>,
+    // アトラスにまとめた場合の [セルマップID][セルID] から (ページID, ページ内のセルID)
+    atlas_ids: Option<Vec<Vec<(usize, usize)>>>,
+}
+
+struct CellMapEntry {
+    name: String,
+    file_name: String,
+    cell_names: Vec<String>,
+}
+
+impl CellMapTable {
+    pub(crate) fn new(project: &sprite_studio::SpriteStudioData) -> Self {
+        let maps = project
+            .cell_maps()
+            .map(|cell_map| CellMapEntry {
+                name: cell_map.name().to_string(),
+                file_name: cell_map.file_name().to_string(),
+                // セルの指定を名前からIDに変更するための情報生成
+                cell_names: cell_map
+                    .cells()
+                    .map(|cell| cell.name().to_string())
+                    .collect(),
+            })
+            .collect();
+        CellMapTable {
+            maps,
+            atlas_ids: None,
+        }
+    }
+
+    // アトラスにまとめる場合は出力するIDをページのものに置き換える
+    pub(crate) fn with_atlas(mut self, layout: &AtlasLayout) -> Self {
+        let mut atlas_ids = self
+            .maps
+            .iter()
+            .map(|map| vec![(0, 0); map.cell_names.len()])
+            .collect::<Vec<_>>();
+        for (page_id, page) in layout.pages.iter().enumerate() {
+            for (sprite_id, cell) in page.cells.iter().enumerate() {
+                atlas_ids[cell.map_id][cell.cell_id] = (page_id, sprite_id);
+            }
+        }
+        self.atlas_ids = Some(atlas_ids);
+        self
+    }
+
+    fn output_id(&self, map_id: usize, cell_id: usize) -> (usize, usize) {
+        match &self.atlas_ids {
+            Some(atlas_ids) => atlas_ids[map_id][cell_id],
+            None => (map_id, cell_id),
+        }
+    }
+
+    fn global_index(&self, file_name: &str) -> Option<usize> {
+        self.maps.iter().position(|map| map.file_name == file_name)
+    }
+
+    // セルマップ名(ファイル名でもよい)とセル名から出力する (全体のセルマップID, セルID) を引く
+    // エフェクトはパックを通さずに名前でセルを指定する
+    pub(crate) fn resolve_by_name(
+        &self,
+        map_name: &str,
+        cell_name: &str,
+    ) -> Result<(usize, usize), ParseAnimationError> {
+        let map_id = self
+            .maps
+            .iter()
+            .position(|map| map.name == map_name || map.file_name == map_name)
+            .ok_or_else(|| ParseAnimationError::UnknownCellMap {
+                name: map_name.to_string(),
+            })?;
+        Ok(self.output_id(map_id, self.cell_id(map_id, cell_name)?))
+    }
+
+    fn cell_id(&self, map_id: usize, cell_name: &str) -> Result<usize, ParseAnimationError> {
+        let map = &self.maps[map_id];
+        map.cell_names
+            .iter()
+            .position(|n| n == cell_name)
+            .ok_or_else(|| ParseAnimationError::UnknownCellName {
+                map: map.file_name.clone(),
+                name: cell_name.to_string(),
+            })
+    }
+
+    // パック内のセルマップ番号から全体のIDへの対応表を作る
+    pub(crate) fn pack_cell_maps(
+        &self,
+        pack: &sprite_studio::AnimationPack,
+    ) -> Result<PackCellMaps, ParseAnimationError> {
+        let global_ids = if pack.cell_map_names().count() > 0 {
+            pack.cell_map_names()
+                .map(|name| {
+                    self.global_index(name)
+                        .ok_or_else(|| ParseAnimationError::UnknownCellMap {
+                            name: name.to_string(),
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            // アニメーション側になければもともとあるセルマップ
+            (0..self.maps.len()).collect()
+        };
+
+        Ok(PackCellMaps {
+            table: self,
+            global_ids,
+        })
+    }
+}
+
+// セルのテクスチャ上の範囲とアトラスの配置
+// 出力する画像・シートとアニメーションのセルIDが食い違わないように、どちらもここで作ったものを使う
+pub(crate) struct CellLayout {
+    // [全体のセルマップID][セルID]
+    pub(crate) rects: Vec<Vec<CellRect>>,
+    pub(crate) trim: Option<TrimSummary>,
+    pub(crate) atlas: Option<AtlasLayout>,
+}
+
+impl CellLayout {
+    pub(crate) fn cell_map_table(&self, project: &sprite_studio::SpriteStudioData) -> CellMapTable {
+        let table = CellMapTable::new(project);
+        match &self.atlas {
+            Some(layout) => table.with_atlas(layout),
+            None => table,
+        }
+    }
+}
+
+// images は project.cell_maps() の順番
+// 画像がなければ切り詰めない。アトラスでなければセルIDは変わらないが、
+// アトラスの配置は切り詰めた範囲で決まるので画像が必要
+pub(crate) fn make_cell_layout(
+    project: &sprite_studio::SpriteStudioData,
+    images: Option<&[RgbaImage]>,
+    options: &ConvertOptions,
+) -> Result<CellLayout, ParseAnimationError> {
+    let (rects, trim) = match (options.trim_alpha, images) {
+        (true, Some(images)) => {
+            let (rects, summary) = trim::trim_cell_rects(project, images);
+            (rects, Some(summary))
+        }
+        (true, None) if options.atlas.is_some() => {
+            return Err(ParseAnimationError::TrimmedAtlasNeedsImages)
+        }
+        _ => (trim::cell_rects(project), None),
+    };
+    let atlas = match &options.atlas {
+        Some(atlas_options) => Some(atlas::layout(project, &rects, atlas_options)?),
+        None => None,
+    };
+    Ok(CellLayout { rects, trim, atlas })
+}
+
+pub(crate) struct PackCellMaps<'a> {
+    table: &'a CellMapTable,
+    global_ids: Vec<usize>,
+}
+
+impl<'a> PackCellMaps<'a> {
+    // パック内のセルマップ番号とセル名から出力する (全体のセルマップID, セルID) を引く
+    pub(crate) fn resolve(
+        &self,
+        local_map_id: usize,
+        cell_name: &str,
+    ) -> Result<(usize, usize), ParseAnimationError> {
+        let map_id = *self.global_ids.get(local_map_id).ok_or(
+            ParseAnimationError::CellMapIndexOutOfRange {
+                map_id: local_map_id,
+                count: self.global_ids.len(),
+            },
+        )?;
+        let cell_id = self.table.cell_id(map_id, cell_name)?;
+        Ok(self.table.output_id(map_id, cell_id))
+    }
+}
+
+pub(crate) fn image_file_name(map_id: usize) -> String {
+    format!("sprite{:03}.png", map_id)
+}
+
+pub(crate) fn sheet_file_name(map_id: usize) -> String {
+    format!("sprite{:03}.sheet.ron", map_id)
+}
+
+pub(crate) fn mesh_file_name(map_id: usize) -> String {
+    format!("sprite{:03}.mesh.ron", map_id)
+}
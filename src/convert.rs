@@ -1,12 +1,23 @@
-use crate::error::ParseAnimationError;
+use crate::{
+    cell_map::{self, CellMapTable, PackCellMaps},
+    curve::{self, CurveKey},
+    effect::{self, Effect, EffectBlend},
+    error::{ConvertError, Location, ParseAnimationError},
+    options::{ConvertOptions, FrameMapping, RotationMode, UserDataMode},
+    particle::{self, EffectPlayKey},
+    report::{Report, Reporter},
+    rotation, skeleton,
+};
 use amethyst_sprite_studio::{
     resource::{animation, data, pack, part},
     traits::animation_file::AnimationFile,
     types::{
-        cell, interpolate, Bounds, EffectKey, EffectKeyBuilder, InstanceKey, InstanceKeyBuilder,
-        LinearColor, PartType, VertexKey, VertexKeyBuilder,
+        cell, interpolate, BlendMode, Bounds, ColorBlend, ColorKey, ColorKeyBuilder, EffectKey,
+        EffectKeyBuilder, InstanceKey, InstanceKeyBuilder, LinearColor, PartType, SignalKey,
+        SignalKeyBuilder, SignalValue, VertexKey, VertexKeyBuilder,
     },
 };
+use image::RgbaImage;
 use std::collections::BTreeMap;
 use std::str::FromStr;
 
@@ -22,24 +33,129 @@ where
     <T::PackKey as FromStr>::Err: failure::Fail,
     <T::AnimationKey as FromStr>::Err: failure::Fail,
 {
-    let cell_map_names = make_cell_names(&project);
+    convert_with_options::<T>(project, &ConvertOptions::default())
+}
+
+// 画像を読まないので、切り詰めてアトラスにまとめるオプションはエラーになる
+pub fn convert_with_options<'a, T>(
+    project: &'a sprite_studio::SpriteStudioData,
+    options: &ConvertOptions,
+) -> Result<data::AnimationData<T>, failure::Error>
+where
+    T: AnimationFile,
+    T::PackKey: FromStr,
+    T::AnimationKey: FromStr,
+    <T::PackKey as FromStr>::Err: failure::Fail,
+    <T::AnimationKey as FromStr>::Err: failure::Fail,
+{
+    let cell_maps = make_cell_map_table(project, None, options, &mut Reporter::fail_fast())?;
+    convert_with_cell_maps::<T>(project, &cell_maps, options)
+}
+
+// 画像の不透明な範囲から作ったアトラスの配置を使う場合は、セルの対応表を外から渡す
+pub(crate) fn convert_with_cell_maps<'a, T>(
+    project: &'a sprite_studio::SpriteStudioData,
+    cell_maps: &CellMapTable,
+    options: &ConvertOptions,
+) -> Result<data::AnimationData<T>, failure::Error>
+where
+    T: AnimationFile,
+    T::PackKey: FromStr,
+    T::AnimationKey: FromStr,
+    <T::PackKey as FromStr>::Err: failure::Fail,
+    <T::AnimationKey as FromStr>::Err: failure::Fail,
+{
+    let mut reporter = Reporter::fail_fast();
     let effect_names = make_effect_names(&project);
+    let effects = make_baked_effects(project, cell_maps, options, &mut reporter)?;
 
-    convert_project::<T>(project, cell_map_names, effect_names)
+    convert_project::<T>(
+        project,
+        cell_maps,
+        effect_names,
+        &effects,
+        options,
+        &Location::default(),
+        &mut reporter,
+    )
 }
 
-fn make_cell_names(project: &sprite_studio::SpriteStudioData) -> BTreeMap<String, Vec<String>> {
-    let mut cell_map_names = BTreeMap::new();
+// 最初のエラーで止めずに、全てのパック・アニメーション・パーツ・キーを検査する
+pub fn validate<'a, T>(
+    project: &'a sprite_studio::SpriteStudioData,
+    options: &ConvertOptions,
+) -> Report
+where
+    T: AnimationFile,
+    T::PackKey: FromStr,
+    T::AnimationKey: FromStr,
+    <T::PackKey as FromStr>::Err: failure::Fail,
+    <T::AnimationKey as FromStr>::Err: failure::Fail,
+{
+    validate_project::<T>(project, None, options)
+}
 
-    for cell_map in project.cell_maps() {
-        // セルの指定を名前からIDに変更するための情報生成
-        let mut cell_names = vec![];
-        for cell in cell_map.cells() {
-            cell_names.push(cell.name().to_string());
-        }
-        cell_map_names.insert(cell_map.file_name().to_string(), cell_names);
+// 切り詰めたアトラスの配置は画像の不透明な範囲で決まるので、画像を渡して検査する
+// images は project.cell_maps() の順番
+pub fn validate_with_images<'a, T>(
+    project: &'a sprite_studio::SpriteStudioData,
+    images: &[RgbaImage],
+    options: &ConvertOptions,
+) -> Report
+where
+    T: AnimationFile,
+    T::PackKey: FromStr,
+    T::AnimationKey: FromStr,
+    <T::PackKey as FromStr>::Err: failure::Fail,
+    <T::AnimationKey as FromStr>::Err: failure::Fail,
+{
+    validate_project::<T>(project, Some(images), options)
+}
+
+fn validate_project<'a, T>(
+    project: &'a sprite_studio::SpriteStudioData,
+    images: Option<&[RgbaImage]>,
+    options: &ConvertOptions,
+) -> Report
+where
+    T: AnimationFile,
+    T::PackKey: FromStr,
+    T::AnimationKey: FromStr,
+    <T::PackKey as FromStr>::Err: failure::Fail,
+    <T::AnimationKey as FromStr>::Err: failure::Fail,
+{
+    let effect_names = make_effect_names(&project);
+    let mut reporter = Reporter::collect_all();
+
+    // collect_all ではエラーにならない
+    let cell_maps = make_cell_map_table(project, images, options, &mut reporter)
+        .unwrap_or_else(|_| CellMapTable::new(&project));
+    // 変換時に別ファイルへ出力するエフェクトとボーンも検査する
+    let effects = effect::convert_effects(project, &cell_maps, &mut reporter).unwrap_or_default();
+    let _ = skeleton::make_skeletons(project, &mut reporter);
+    let baked_effects = if options.bake_effects {
+        particle::warn_unbaked(&effects, &mut reporter);
+        &effects[..]
+    } else {
+        &[]
+    };
+    let result = convert_project::<T>(
+        project,
+        &cell_maps,
+        effect_names,
+        baked_effects,
+        options,
+        &Location::default(),
+        &mut reporter,
+    );
+    let mut report = reporter.into_report();
+    if let Err(err) = result {
+        report.errors.push(match err.downcast::<ConvertError>() {
+            Ok(err) => err,
+            Err(err) => Location::default().error(err),
+        });
     }
-    cell_map_names
+    report
 }
 
 fn make_effect_names(project: &sprite_studio::SpriteStudioData) -> Vec<String> {
@@ -52,10 +168,46 @@ fn make_effect_names(project: &sprite_studio::SpriteStudioData) -> Vec<String> {
     effect_names
 }
 
+// アトラスにまとめる場合はセルのIDをページのものに置き換える
+// 画像を出力するときと同じ make_cell_layout で配置を計算する
+fn make_cell_map_table(
+    project: &sprite_studio::SpriteStudioData,
+    images: Option<&[RgbaImage]>,
+    options: &ConvertOptions,
+    reporter: &mut Reporter,
+) -> Result<CellMapTable, ConvertError> {
+    match cell_map::make_cell_layout(project, images, options) {
+        Ok(layout) => Ok(layout.cell_map_table(project)),
+        Err(err) => {
+            reporter.error(Location::default().error(err))?;
+            Ok(CellMapTable::new(project))
+        }
+    }
+}
+
+// エフェクトを焼き込む場合だけパーティクルの計算に使うエミッター定義を作る
+fn make_baked_effects(
+    project: &sprite_studio::SpriteStudioData,
+    cell_maps: &CellMapTable,
+    options: &ConvertOptions,
+    reporter: &mut Reporter,
+) -> Result<Vec<Effect>, ConvertError> {
+    if options.bake_effects == false {
+        return Ok(vec![]);
+    }
+    let effects = effect::convert_effects(project, cell_maps, reporter)?;
+    particle::warn_unbaked(&effects, reporter);
+    Ok(effects)
+}
+
 fn convert_project<'a, T>(
     project: &'a sprite_studio::SpriteStudioData,
-    cell_map_names: BTreeMap<String, Vec<String>>,
+    cell_maps: &CellMapTable,
     effect_names: Vec<String>,
+    effects: &[Effect],
+    options: &ConvertOptions,
+    location: &Location,
+    reporter: &mut Reporter,
 ) -> Result<data::AnimationData<T>, failure::Error>
 where
     T: AnimationFile,
@@ -64,33 +216,68 @@ where
     <T::PackKey as FromStr>::Err: failure::Fail,
     <T::AnimationKey as FromStr>::Err: failure::Fail,
 {
+    if let Some(fps) = options.target_fps {
+        if SUPPORTED_FPS.contains(&fps) == false {
+            Err(location.error(ParseAnimationError::NonSupportedFps { fps }))?;
+        }
+    }
+
+    // インスタンスのオフセットは参照先のアニメーションのフレームなので、参照先のFPSで変換する
+    let animation_fps = project
+        .packs()
+        .flat_map(|pack| {
+            pack.animations().map(move |animation| {
+                (
+                    (pack.name().to_string(), animation.name().to_string()),
+                    animation.setting().fps(),
+                )
+            })
+        })
+        .collect::<BTreeMap<_, _>>();
+
     let mut anim_packs = BTreeMap::new();
     for pack in project.packs() {
-        let mut pack_cell_map_names = vec![];
-
-        if pack.cell_map_names().count() > 0 {
-            for in_pack_cell_map in pack.cell_map_names() {
-                pack_cell_map_names.push(cell_map_names[in_pack_cell_map].clone());
-            }
-        } else {
-            // アニメーション側になければもともとあるセルマップ
-            for (_, cell_map) in &cell_map_names {
-                pack_cell_map_names.push(cell_map.clone());
+        let location = location.pack(pack.name());
+        let pack_cell_maps = match cell_maps.pack_cell_maps(pack) {
+            Ok(pack_cell_maps) => pack_cell_maps,
+            Err(err) => {
+                reporter.error(location.error(err))?;
+                continue;
             }
-        }
+        };
 
         log::info!("convert pack start: {}", pack.name());
-        let anim_pack = convert_pack::<T>(pack, &pack_cell_map_names, &effect_names)?;
-        anim_packs.insert(T::PackKey::from_str(pack.name())?, anim_pack);
+        let anim_pack = convert_pack::<T>(
+            pack,
+            &pack_cell_maps,
+            &effect_names,
+            effects,
+            &animation_fps,
+            options,
+            &location,
+            reporter,
+        )?;
+        match T::PackKey::from_str(pack.name()) {
+            Ok(pack_key) => {
+                anim_packs.insert(pack_key, anim_pack);
+            }
+            Err(err) => reporter.error(location.error(err))?,
+        }
     }
 
     Ok(data::AnimationDataBuilder::new(anim_packs).build())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn convert_pack<'a, T>(
     pack: &'a sprite_studio::AnimationPack,
-    cell_map_names: &Vec<Vec<String>>,
+    cell_maps: &PackCellMaps,
     effect_names: &Vec<String>,
+    effects: &[Effect],
+    animation_fps: &BTreeMap<(String, String), u32>,
+    options: &ConvertOptions,
+    location: &Location,
+    reporter: &mut Reporter,
 ) -> Result<pack::Pack<T::UserData, T::PackKey, T::AnimationKey>, failure::Error>
 where
     T: AnimationFile,
@@ -100,39 +287,175 @@ where
     <T::AnimationKey as FromStr>::Err: failure::Fail,
 {
     let mut parts = vec![];
+    let inherits = resolve_inherits(pack);
 
-    for part in pack.parts() {
-        let (_, part) = convert_part::<T>(part, effect_names)?;
+    for (part, inherit) in pack.parts().zip(&inherits) {
+        match convert_part::<T>(part, inherit, effect_names, location) {
+            Ok((_, part)) => parts.push(part),
+            Err(err) => {
+                reporter.error(err)?;
+                // パーツIDがずれないように、変換できなかったパーツの場所は空のパーツで埋める
+                parts.push(part::PartBuilder::new(part.name(), PartType::Null).build());
+            }
+        }
+    }
 
-        parts.push(part);
+    // 焼き込むエフェクトパーツの子にパーティクル用のパーツを追加する
+    let mut bakes = vec![];
+    if options.bake_effects {
+        for (part_id, effect) in particle::baked_effect_parts(pack, effects) {
+            let first_part_id = parts.len();
+            let part_name = pack
+                .parts()
+                .nth(part_id)
+                .map(|part| part.name())
+                .unwrap_or("");
+            // 加算のエミッターのパーティクルは加算で描画する
+            for (slot, emitter) in particle::slot_emitters(effect).enumerate() {
+                let blend_mode = match emitter.blend {
+                    EffectBlend::Mix => BlendMode::Mix,
+                    EffectBlend::Add => BlendMode::Add,
+                };
+                parts.push(
+                    part::PartBuilder::new(
+                        &particle::slot_part_name(part_name, slot),
+                        PartType::Normal,
+                    )
+                    .parent_id(part_id as u32)
+                    .blend_mode(blend_mode)
+                    .build(),
+                );
+            }
+            bakes.push(EffectBake {
+                part_id,
+                first_part_id,
+                effect,
+            });
+        }
     }
 
+    // インスタンスパーツが参照するアニメーションのFPS
+    let instance_fps = pack
+        .parts()
+        .map(|part| {
+            part.refference_animation()
+                .and_then(|(pack_name, anim_name)| {
+                    animation_fps
+                        .get(&(pack_name.to_string(), anim_name.to_string()))
+                        .cloned()
+                })
+        })
+        .collect::<Vec<_>>();
+
     let mut animations = BTreeMap::new();
     let mut setup = None;
 
     for animation in pack.animations() {
+        let location = location.animation(animation.name());
+        let fps = animation.setting().fps();
+        let timing = FrameTiming::new(fps, options);
         if animation.name() == "Setup" {
             log::info!("convert animation start: {}", animation.name());
-            setup = convert_animation::<T>(&parts, animation, cell_map_names)?.into();
+            setup = convert_animation::<T>(
+                &parts,
+                animation,
+                cell_maps,
+                &bakes,
+                &instance_fps,
+                &timing,
+                options,
+                &location,
+                reporter,
+            )?
+            .into();
             continue;
         } else {
             log::info!("convert animation start: {}", animation.name());
-            let fps = animation.setting().fps();
-            if SUPPORTED_FPS.contains(&fps) == false {
-                Err(ParseAnimationError::NonSupportedFps { fps })?;
+            // 変換先のFPSが指定されていればそちらに合わせる
+            if options.target_fps.is_none() && SUPPORTED_FPS.contains(&fps) == false {
+                reporter.error(location.error(ParseAnimationError::NonSupportedFps { fps }))?;
+            }
+            let anim = convert_animation::<T>(
+                &parts,
+                animation,
+                cell_maps,
+                &bakes,
+                &instance_fps,
+                &timing,
+                options,
+                &location,
+                reporter,
+            )?;
+            match T::AnimationKey::from_str(animation.name()) {
+                Ok(animation_key) => {
+                    animations.insert(animation_key, anim);
+                }
+                Err(err) => reporter.error(location.error(err))?,
             }
-            let anim = convert_animation::<T>(&parts, animation, cell_map_names)?;
-            animations.insert(T::AnimationKey::from_str(animation.name())?, anim);
         }
     }
 
     Ok(pack::PackBuilder::new(parts, animations, setup).build())
 }
 
+// 親の値をどれだけ引き継ぐか
+// 反転と非表示は 0 でなければ引き継ぐ
+#[derive(Debug, Clone, Copy)]
+struct InheritRates {
+    alpha: f32,
+    flip_h: f32,
+    flip_v: f32,
+    hide: f32,
+}
+
+impl Default for InheritRates {
+    fn default() -> Self {
+        InheritRates {
+            alpha: 1.,
+            flip_h: 1.,
+            flip_v: 1.,
+            hide: 1.,
+        }
+    }
+}
+
+// 「親の継承設定に従う」パーツは親の設定をたどって実際の値にする
+fn resolve_inherits(pack: &sprite_studio::AnimationPack) -> Vec<InheritRates> {
+    let mut inherits: Vec<InheritRates> = vec![];
+    // パーツは親が先に並んでいる
+    for part in pack.parts() {
+        let parent = if part.parent() < 0 {
+            None
+        } else {
+            inherits.get(part.parent() as usize).copied()
+        };
+        let rates = match part.inherit_type() {
+            sprite_studio::InheritType::Parent => parent.unwrap_or_default(),
+            sprite_studio::InheritType::SelfRates => {
+                part.inherit_rates()
+                    .fold(InheritRates::default(), |mut rates, (tag, rate)| {
+                        match tag {
+                            sprite_studio::AttributeTag::Alpha => rates.alpha = rate,
+                            sprite_studio::AttributeTag::Fliph => rates.flip_h = rate,
+                            sprite_studio::AttributeTag::Flipv => rates.flip_v = rate,
+                            sprite_studio::AttributeTag::Hide => rates.hide = rate,
+                            _ => {}
+                        }
+                        rates
+                    })
+            }
+        };
+        inherits.push(rates);
+    }
+    inherits
+}
+
 fn convert_part<T>(
     part: &sprite_studio::Part,
+    inherit: &InheritRates,
     effect_names: &Vec<String>,
-) -> Result<(u32, part::Part<T::PackKey, T::AnimationKey>), failure::Error>
+    location: &Location,
+) -> Result<(u32, part::Part<T::PackKey, T::AnimationKey>), ConvertError>
 where
     T: AnimationFile,
     T::PackKey: FromStr,
@@ -140,11 +463,12 @@ where
     <T::PackKey as FromStr>::Err: failure::Fail,
     <T::AnimationKey as FromStr>::Err: failure::Fail,
 {
+    let location = location.part(part.name());
     let part_id = if part.index() < 0 {
-        Err(ParseAnimationError::PartIndexError(
+        Err(location.error(ParseAnimationError::PartIndexError(
             part.name().into(),
             part.index(),
-        ))
+        )))
     } else {
         Ok(part.index() as u32)
     }?;
@@ -172,8 +496,8 @@ where
 
     let builder = match part.refference_animation() {
         Some((pack_name, anim_name)) => builder.refference_animation_name(
-            T::PackKey::from_str(pack_name)?,
-            T::AnimationKey::from_str(anim_name)?,
+            T::PackKey::from_str(pack_name).map_err(|err| location.error(err))?,
+            T::AnimationKey::from_str(anim_name).map_err(|err| location.error(err))?,
         ),
         _ => builder,
     };
@@ -196,20 +520,102 @@ where
         builder
     };
 
+    // パーツを描画するときのアルファブレンド
+    let blend_mode = match part.alpha_blend_type() {
+        sprite_studio::BlendType::Mix => BlendMode::Mix,
+        sprite_studio::BlendType::Mul => BlendMode::Multiply,
+        sprite_studio::BlendType::Add => BlendMode::Add,
+        sprite_studio::BlendType::Sub => BlendMode::Subtract,
+        sprite_studio::BlendType::MulAlpha => BlendMode::MultiplyAlpha,
+        sprite_studio::BlendType::Screen => BlendMode::Screen,
+        sprite_studio::BlendType::Exclusion => BlendMode::Exclusion,
+        sprite_studio::BlendType::Invert => BlendMode::Invert,
+    };
+    let builder = builder.blend_mode(blend_mode);
+
+    let builder = builder
+        .inherit_alpha(inherit.alpha)
+        .inherit_flip_h(inherit.flip_h != 0.)
+        .inherit_flip_v(inherit.flip_v != 0.)
+        .inherit_hide(inherit.hide != 0.);
+
+    // マスクパーツの影響を受けるかどうか
+    let builder = builder.mask_influence(part.mask_influence());
+
     Ok((part_id, builder.bounds(bounds).build()))
 }
 
+// 元のFPSのフレーム番号を変換後のFPSのフレーム番号に合わせる
+pub(crate) struct FrameTiming {
+    pub(crate) source_fps: u32,
+    pub(crate) target_fps: u32,
+    mapping: FrameMapping,
+}
+
+impl FrameTiming {
+    pub(crate) fn new(source_fps: u32, options: &ConvertOptions) -> Self {
+        FrameTiming {
+            source_fps,
+            target_fps: options.target_fps.unwrap_or(source_fps),
+            mapping: options.frame_mapping,
+        }
+    }
+
+    // Exact ではキーの時間より前に値が変わらないように切り上げる
+    pub(crate) fn frame(&self, time: usize) -> usize {
+        if self.source_fps == self.target_fps {
+            return time;
+        }
+        let scaled = time * self.target_fps as usize;
+        let source_fps = self.source_fps as usize;
+        match self.mapping {
+            FrameMapping::Nearest => (scaled + source_fps / 2) / source_fps,
+            FrameMapping::Exact => (scaled + source_fps - 1) / source_fps,
+        }
+    }
+
+    fn lands_on_frame(&self, time: usize) -> bool {
+        (time * self.target_fps as usize) % self.source_fps as usize == 0
+    }
+
+    // 変換後のフレームの元のFPSでの時間
+    pub(crate) fn source_time(&self, frame: usize) -> f32 {
+        frame as f32 * self.source_fps as f32 / self.target_fps as f32
+    }
+
+    // 最後のフレームまで再生時間が変わらないように切り上げる
+    pub(crate) fn frame_count(&self, count: usize) -> usize {
+        let scaled = count * self.target_fps as usize;
+        let source_fps = self.source_fps as usize;
+        (scaled + source_fps - 1) / source_fps
+    }
+}
+
+// パーティクルを焼き込むエフェクトパーツと、その子に追加したパーツの先頭
+struct EffectBake<'a> {
+    part_id: usize,
+    first_part_id: usize,
+    effect: &'a Effect,
+}
+
 // SpriteStudio ではZ座標はPositionとPriorityがあるのでどっちかだけ許したい
 enum PositionZType {
     Position,
     Priority,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn convert_animation<T>(
     parts: &Vec<part::Part<T::PackKey, T::AnimationKey>>,
     animation: &sprite_studio::Animation,
-    cell_map_names: &Vec<Vec<String>>,
-) -> Result<animation::Animation<T::UserData>, ParseAnimationError>
+    cell_maps: &PackCellMaps,
+    bakes: &[EffectBake],
+    instance_fps: &[Option<u32>],
+    timing: &FrameTiming,
+    options: &ConvertOptions,
+    location: &Location,
+    reporter: &mut Reporter,
+) -> Result<animation::Animation<T::UserData>, ConvertError>
 where
     T: AnimationFile,
     T::PackKey: FromStr,
@@ -218,74 +624,243 @@ where
     <T::AnimationKey as FromStr>::Err: failure::Fail,
 {
     // パーツごとにアニメーションキーフレームをまとめる
-    // サポートしてるFPSか
-    let fps = animation.setting().fps();
-
-    let mut builder = animation::AnimationBuilder::new(
-        parts.len(),
-        animation.setting().count() as usize,
-        fps as usize,
-    );
+    let frame_count = timing.frame_count(animation.setting().count() as usize);
+    let mut builder =
+        animation::AnimationBuilder::new(parts.len(), frame_count, timing.target_fps as usize);
 
     // 最初に出た方がZ座標の基準
     let mut position_z_type = None;
 
+    // パックにないパーツのアニメーションは変換されない
+    for part_anim in animation.part_animes() {
+        if parts.iter().all(|part| part.name() != part_anim.name()) {
+            reporter.warn(
+                &location.part(part_anim.name()),
+                "part animation has no matching part in pack",
+            );
+        }
+    }
+
     for (part_id, part) in parts.iter().enumerate() {
         // パックにあるパーツと同じ名前のアニメーションデータがあるか探す
         let part_anim = animation.part_animes().find(|pa| part.name() == pa.name());
+        let bake = bakes.iter().find(|bake| bake.part_id == part_id);
+        // 参照先が見つからなければこのアニメーションと同じFPSとみなす
+        let instance_timing = instance_fps
+            .get(part_id)
+            .and_then(|fps| *fps)
+            .map(|fps| FrameTiming::new(fps, options));
+        let instance_timing = instance_timing.as_ref().unwrap_or(timing);
+        let mut effect_keys = vec![];
         if let Some(part_anim) = part_anim {
+            let location = location.part(part.name());
             for attr in part_anim.attributes() {
+                let location = location.attribute(*attr.tag());
+
+                // X/Y軸回転を焼き込む場合は全キーを集めてから変換する
+                let bake_axis = match options.rotation_mode {
+                    RotationMode::Bake => rotation::Axis::from_tag(attr.tag()),
+                    RotationMode::Track => None,
+                };
+                if let Some(axis) = bake_axis {
+                    if part_anim.attributes().any(|a| axis.conflicts_with(a.tag())) {
+                        reporter.error(location.error(
+                            ParseAnimationError::ConflictRotationBake {
+                                attribute: *attr.tag(),
+                            },
+                        ))?;
+                        continue;
+                    }
+                }
+                let mut rotation_keys = vec![];
+
+                // Exact で変換後のフレームに乗らないキーがある数値の属性は、
+                // キーの時間どおりになるように変換後の毎フレームでカーブを評価する
+                let resample = match options.frame_mapping {
+                    FrameMapping::Exact if bake_axis.is_none() => {
+                        float_key_setter::<T::UserData>(attr.tag()).filter(|_| {
+                            attr.keys()
+                                .any(|key| timing.lands_on_frame(key.time() as usize) == false)
+                        })
+                    }
+                    _ => None,
+                };
+                let mut curve_keys = vec![];
+
+                let mut prev_frame = None;
                 for key in attr.keys() {
-                    convert_key_value(
+                    let location = location.frame(key.time() as usize);
+                    if resample.is_some() {
+                        match convert_float(key).and_then(|value| CurveKey::from_key(key, value)) {
+                            Ok(curve_key) => curve_keys.push(curve_key),
+                            Err(err) => reporter.error(location.error(err))?,
+                        }
+                        continue;
+                    }
+
+                    let frame = timing.frame(key.time() as usize);
+                    // FPSを下げると複数のキーが同じフレームに重なることがある
+                    if prev_frame == Some(frame) {
+                        reporter.warn(
+                            &location,
+                            format!("key overwrites previous key at frame {}", frame),
+                        );
+                    }
+                    prev_frame = Some(frame);
+
+                    if bake_axis.is_some() {
+                        match convert_float(key).and_then(|angle| CurveKey::from_key(key, angle)) {
+                            Ok(curve_key) => rotation_keys.push(curve_key),
+                            Err(err) => reporter.error(location.error(err))?,
+                        }
+                        continue;
+                    }
+
+                    // 焼き込むエフェクトは再生位置だけ集めてパーティクルのキーにする
+                    if let (sprite_studio::AttributeTag::Effect, Some(_)) = (attr.tag(), bake) {
+                        effect_keys.push(convert_effect_play_key(key, frame));
+                        continue;
+                    }
+
+                    if let Err(err) = convert_key_value(
                         &mut builder,
                         part_id,
                         attr.tag(),
                         key,
-                        cell_map_names,
+                        frame,
+                        timing,
+                        instance_timing,
+                        cell_maps,
+                        options.user_data,
                         &mut position_z_type,
-                    )?;
+                    ) {
+                        reporter.error(location.error(err))?;
+                    }
+                }
+
+                if let Some(axis) = bake_axis {
+                    rotation::bake_rotation(&mut builder, part_id, axis, &rotation_keys, timing);
+                }
+                if let Some(set_key) = resample {
+                    resample_curve(&mut builder, part_id, set_key, &curve_keys, timing);
                 }
             }
         }
+
+        // キーがなくてもパーティクルのパーツは非表示にする
+        if let Some(bake) = bake {
+            particle::bake_effect(
+                &mut builder,
+                bake.first_part_id,
+                bake.effect,
+                &effect_keys,
+                frame_count,
+                timing.target_fps,
+            );
+        }
     }
 
     Ok(builder.build())
 }
 
+// 補間できる数値の属性のキーをセットする
+type FloatKeySetter<U> =
+    fn(&mut animation::AnimationBuilder<U>, usize, usize, interpolate::Interpolation, f32);
+
+// Z座標は Position と Priority の排他があるので含めない
+fn float_key_setter<U>(tag: &sprite_studio::AttributeTag) -> Option<FloatKeySetter<U>> {
+    use sprite_studio::AttributeTag as Tag;
+    let setter: FloatKeySetter<U> = match tag {
+        Tag::Posx => animation::AnimationBuilder::add_pos_x,
+        Tag::Posy => animation::AnimationBuilder::add_pos_y,
+        Tag::Rotx => animation::AnimationBuilder::add_rotated_x,
+        Tag::Roty => animation::AnimationBuilder::add_rotated_y,
+        Tag::Rotz => animation::AnimationBuilder::add_rotated,
+        Tag::Sclx => animation::AnimationBuilder::add_scale_x,
+        Tag::Scly => animation::AnimationBuilder::add_scale_y,
+        Tag::Alpha => animation::AnimationBuilder::add_alpha,
+        // ローカルスケールとローカル不透明度は自分だけに掛かり子パーツには継承しない
+        // 武器だけ縮めて付いているエフェクトはそのままにする、といった用途
+        Tag::Lscx => animation::AnimationBuilder::add_local_scale_x,
+        Tag::Lscy => animation::AnimationBuilder::add_local_scale_y,
+        Tag::Lalpha => animation::AnimationBuilder::add_local_alpha,
+        // マスクの閾値(0 ~ 255)、マスクパーツのセルのアルファがこれを超えた部分を切り抜く
+        Tag::Mask => animation::AnimationBuilder::add_mask_threshold,
+        // 原点オフセットはシートの offsets に入れたセルの原点からの相対値
+        // セルのサイズを1.0とした割合なのでセルが変わっても同じ意味になる
+        Tag::Pvtx => animation::AnimationBuilder::add_pivot_x,
+        Tag::Pvty => animation::AnimationBuilder::add_pivot_y,
+        // サイズはスケールとは別に表示する矩形の大きさをピクセルで指定する
+        Tag::Sizx => animation::AnimationBuilder::add_size_x,
+        Tag::Sizy => animation::AnimationBuilder::add_size_y,
+        // アンカーは親パーツの矩形内での基準位置(-1.0 ~ 1.0)
+        Tag::Ancx => animation::AnimationBuilder::add_anchor_x,
+        Tag::Ancy => animation::AnimationBuilder::add_anchor_y,
+        // UVアニメーションはセルの矩形内でテクスチャ座標をずらす
+        // 移動量はセルのサイズを1.0とした割合、回転(度)と拡縮はセルの中心が基準
+        // 表示する矩形自体は変わらないので水面やベルトコンベアのスクロールに使う
+        Tag::Uvtx => animation::AnimationBuilder::add_uv_translate_x,
+        Tag::Uvty => animation::AnimationBuilder::add_uv_translate_y,
+        Tag::Uvrz => animation::AnimationBuilder::add_uv_rotated,
+        Tag::Uvsx => animation::AnimationBuilder::add_uv_scale_x,
+        Tag::Uvsy => animation::AnimationBuilder::add_uv_scale_y,
+        _ => return None,
+    };
+    Some(setter)
+}
+
+// 変換後の各フレームの元のFPSでの時間でカーブを評価してキーにする
+// 次のキーまで値が変わらない区間は Step にして補間させない
+fn resample_curve<U>(
+    builder: &mut animation::AnimationBuilder<U>,
+    part_id: usize,
+    set_key: FloatKeySetter<U>,
+    keys: &[CurveKey],
+    timing: &FrameTiming,
+) {
+    let (first, last) = match (keys.first(), keys.last()) {
+        (Some(first), Some(last)) => (first.time as usize, last.time as usize),
+        _ => return,
+    };
+    for frame in timing.frame(first)..=timing.frame(last) {
+        let time = timing.source_time(frame);
+        let interpolation = if curve::holds(keys, time) {
+            interpolate::Interpolation::Step
+        } else {
+            interpolate::Interpolation::Linear
+        };
+        set_key(
+            builder,
+            part_id,
+            frame,
+            interpolation,
+            curve::sample(keys, time),
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn convert_key_value<U: serde::de::DeserializeOwned>(
     builder: &mut animation::AnimationBuilder<U>,
     part_id: usize,
     tag: &sprite_studio::AttributeTag,
     key: &sprite_studio::KeyValue,
-    cell_map_names: &Vec<Vec<String>>,
+    frame: usize,
+    timing: &FrameTiming,
+    instance_timing: &FrameTiming,
+    cell_maps: &PackCellMaps,
+    user_data: UserDataMode,
     position_z_type: &mut Option<PositionZType>,
 ) -> Result<(), ParseAnimationError> {
-    use interpolate::Interpolation;
-    use sprite_studio::Interpolation as SsInter;
-    let interpolation = match key.interpolation() {
-        SsInter::Linear => Interpolation::Linear,
-        SsInter::Hermite => Interpolation::Hermite,
-        SsInter::Bezier => Interpolation::Bezier,
-        SsInter::Acceleration => Interpolation::Acceleration,
-        SsInter::Deceleration => Interpolation::Deceleration,
-        SsInter::Step => Interpolation::Step,
-    };
-    let frame = key.time() as usize;
+    let interpolation = convert_interpolation(key, timing)?;
+    if let Some(set_key) = float_key_setter(tag) {
+        set_key(builder, part_id, frame, interpolation, convert_float(key)?);
+        return Ok(());
+    }
     // タグに応じてキーフレームをセットする
     match tag {
         sprite_studio::AttributeTag::Cell => {
-            builder.add_cell(
-                part_id,
-                frame,
-                interpolation,
-                convert_cell(key, cell_map_names)?,
-            );
-        }
-        sprite_studio::AttributeTag::Posx => {
-            builder.add_pos_x(part_id, frame, interpolation, convert_float(key)?);
-        }
-        sprite_studio::AttributeTag::Posy => {
-            builder.add_pos_y(part_id, frame, interpolation, convert_float(key)?);
+            builder.add_cell(part_id, frame, interpolation, convert_cell(key, cell_maps)?);
         }
         sprite_studio::AttributeTag::Posz => {
             if let Some(PositionZType::Priority) = position_z_type {
@@ -295,18 +870,6 @@ fn convert_key_value<U: serde::de::DeserializeOwned>(
                 *position_z_type = Some(PositionZType::Position);
             }
         }
-        sprite_studio::AttributeTag::Rotz => {
-            builder.add_rotated(part_id, frame, interpolation, convert_float(key)?);
-        }
-        sprite_studio::AttributeTag::Sclx => {
-            builder.add_scale_x(part_id, frame, interpolation, convert_float(key)?);
-        }
-        sprite_studio::AttributeTag::Scly => {
-            builder.add_scale_y(part_id, frame, interpolation, convert_float(key)?);
-        }
-        sprite_studio::AttributeTag::Alpha => {
-            builder.add_alpha(part_id, frame, interpolation, convert_float(key)?);
-        }
         sprite_studio::AttributeTag::Prio => {
             if let Some(PositionZType::Position) = position_z_type {
                 Err(ParseAnimationError::ConflictPositionZ)?;
@@ -332,11 +895,22 @@ fn convert_key_value<U: serde::de::DeserializeOwned>(
         sprite_studio::AttributeTag::Vertex => {
             builder.add_vertex(part_id, frame, interpolation, convert_vertex(key)?);
         }
+        sprite_studio::AttributeTag::Deform => {
+            builder.add_deform(part_id, frame, interpolation, convert_deform(key)?);
+        }
         sprite_studio::AttributeTag::User => {
-            builder.add_user(part_id, frame, interpolation, convert_user(key)?);
+            builder.add_user(part_id, frame, interpolation, convert_user(key, user_data)?);
+        }
+        sprite_studio::AttributeTag::Signal => {
+            builder.add_signal(part_id, frame, interpolation, convert_signal(key)?);
         }
         sprite_studio::AttributeTag::Instance => {
-            builder.add_instance(part_id, frame, interpolation, convert_instance_key(key));
+            builder.add_instance(
+                part_id,
+                frame,
+                interpolation,
+                convert_instance_key(key, instance_timing),
+            );
         }
         sprite_studio::AttributeTag::Effect => {
             builder.add_effect(part_id, frame, interpolation, convert_effect(key)?);
@@ -348,31 +922,71 @@ fn convert_key_value<U: serde::de::DeserializeOwned>(
     Ok(())
 }
 
+fn convert_interpolation(
+    key: &sprite_studio::KeyValue,
+    timing: &FrameTiming,
+) -> Result<interpolate::Interpolation, ParseAnimationError> {
+    use interpolate::Interpolation;
+    use sprite_studio::Interpolation as SsInter;
+    let interpolation = match key.interpolation() {
+        SsInter::Linear => Interpolation::Linear,
+        SsInter::Hermite => {
+            // エルミートはカーブの値部分を始点と終点の接線として使う
+            let (_, start, _, end) = key.curve().ok_or(ParseAnimationError::NotSetCurve)?;
+            Interpolation::Hermite { start, end }
+        }
+        SsInter::Bezier => {
+            // ベジェは制御点をキーからの相対(時間, 値)として持つ
+            // 時間はフレーム数なので変換後のFPSに合わせる
+            let (start_time, start_value, end_time, end_value) =
+                key.curve().ok_or(ParseAnimationError::NotSetCurve)?;
+            let scale = timing.target_fps as f32 / timing.source_fps as f32;
+            Interpolation::Bezier {
+                start: (start_time * scale, start_value),
+                end: (end_time * scale, end_value),
+            }
+        }
+        SsInter::Acceleration => Interpolation::Acceleration,
+        SsInter::Deceleration => Interpolation::Deceleration,
+        SsInter::Step => Interpolation::Step,
+    };
+    Ok(interpolation)
+}
+
 fn convert_cell(
     key_values: &sprite_studio::KeyValue,
-    cell_map_names: &Vec<Vec<String>>,
+    cell_maps: &PackCellMaps,
 ) -> Result<cell::Cell, ParseAnimationError> {
-    let map_id = key_values
+    let (map_id, cell_id) = cell_key_ids(key_values, cell_maps)?;
+    Ok(cell::CellBuilder::new(map_id, cell_id).build())
+}
+
+// セルのキーが指す (全体のセルマップID, セルID)
+pub(crate) fn cell_key_ids(
+    key_values: &sprite_studio::KeyValue,
+    cell_maps: &PackCellMaps,
+) -> Result<(usize, usize), ParseAnimationError> {
+    let local_map_id = key_values
         .values()
         .find_map(|v| match v {
             &sprite_studio::ValueType::MapId(id) => Some(id as usize),
             _ => None,
         })
         .ok_or(ParseAnimationError::NotSetMapId)?;
-    let cell_id = key_values
+    let cell_name = key_values
         .values()
         .find_map(|v| match v {
-            sprite_studio::ValueType::Name(name) => {
-                cell_map_names[map_id].iter().position(|n| n == name)
-            }
+            sprite_studio::ValueType::Name(name) => Some(name),
             _ => None,
         })
         .ok_or(ParseAnimationError::NotSetCellName)?;
-
-    Ok(cell::CellBuilder::new(map_id, cell_id).build())
+    // パック内のセルマップ番号を出力する画像・シートと同じ全体のIDに変換する
+    cell_maps.resolve(local_map_id, cell_name)
 }
 
-fn convert_float(key_values: &sprite_studio::KeyValue) -> Result<f32, ParseAnimationError> {
+pub(crate) fn convert_float(
+    key_values: &sprite_studio::KeyValue,
+) -> Result<f32, ParseAnimationError> {
     key_values
         .values()
         .find_map(|v| match v {
@@ -382,14 +996,58 @@ fn convert_float(key_values: &sprite_studio::KeyValue) -> Result<f32, ParseAnima
         .ok_or(ParseAnimationError::NotSetFloatValue)
 }
 
-fn convert_color(key_values: &sprite_studio::KeyValue) -> Result<LinearColor, ParseAnimationError> {
-    key_values
-        .values()
-        .find_map(|v| match v {
-            &sprite_studio::ValueType::Color(r, g, b, a) => Some(LinearColor(r, g, b, a)),
-            _ => None,
-        })
-        .ok_or(ParseAnimationError::NotSetColor)
+// カラーはセルの色とどう合成するかと、パーツ全体か頂点ごとかの指定を持つ
+// 頂点ごとの場合は 左上, 右上, 左下, 右下 の順に色と割合が並んでいる
+fn convert_color(key_values: &sprite_studio::KeyValue) -> Result<ColorKey, ParseAnimationError> {
+    let mut colors = vec![];
+    let mut rates = vec![];
+    let mut blend = ColorBlend::Mix;
+    let mut per_vertex = false;
+    for v in key_values.values() {
+        match v {
+            &sprite_studio::ValueType::Color(r, g, b, a) => colors.push(LinearColor(r, g, b, a)),
+            &sprite_studio::ValueType::Rate(rate) => rates.push(rate),
+            &sprite_studio::ValueType::ColorBlendType(blend_type) => {
+                blend = match blend_type {
+                    sprite_studio::ColorBlendType::Mix => ColorBlend::Mix,
+                    sprite_studio::ColorBlendType::Mul => ColorBlend::Multiply,
+                    sprite_studio::ColorBlendType::Add => ColorBlend::Add,
+                    sprite_studio::ColorBlendType::Sub => ColorBlend::Subtract,
+                }
+            }
+            &sprite_studio::ValueType::ColorTarget(target) => {
+                per_vertex = match target {
+                    sprite_studio::ColorTarget::Whole => false,
+                    sprite_studio::ColorTarget::Vertex => true,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let rate = |idx: usize| rates.get(idx).copied().unwrap_or(1.);
+    let color = colors
+        .first()
+        .cloned()
+        .ok_or(ParseAnimationError::NotSetColor)?;
+    let builder = ColorKeyBuilder::new(color).rate(rate(0)).blend(blend);
+
+    let builder = if per_vertex {
+        if colors.len() < 4 {
+            return Err(ParseAnimationError::NotSetVertexColor {
+                count: colors.len(),
+            });
+        }
+        builder.vertex_colors([
+            (colors[0].clone(), rate(0)),
+            (colors[1].clone(), rate(1)),
+            (colors[2].clone(), rate(2)),
+            (colors[3].clone(), rate(3)),
+        ])
+    } else {
+        builder
+    };
+    Ok(builder.build())
 }
 
 fn convert_bool(key_values: &sprite_studio::KeyValue) -> Result<bool, ParseAnimationError> {
@@ -402,13 +1060,18 @@ fn convert_bool(key_values: &sprite_studio::KeyValue) -> Result<bool, ParseAnima
         .ok_or(ParseAnimationError::NotSetBoolValue)
 }
 
-fn convert_instance_key(key_values: &sprite_studio::KeyValue) -> InstanceKey {
+// 開始・終了オフセットは参照先のアニメーションのフレームなので、参照先と同じように変換する
+fn convert_instance_key(key_values: &sprite_studio::KeyValue, timing: &FrameTiming) -> InstanceKey {
     key_values
         .values()
         .fold(InstanceKeyBuilder::new(), |builder, v| match v {
             &sprite_studio::ValueType::LoopNum(num) => builder.loop_num(num as usize),
-            &sprite_studio::ValueType::StartOffset(ofs) => builder.start_offset(ofs as usize),
-            &sprite_studio::ValueType::EndOffset(ofs) => builder.end_offset(ofs as usize),
+            &sprite_studio::ValueType::StartOffset(ofs) => {
+                builder.start_offset(timing.frame(ofs as usize))
+            }
+            &sprite_studio::ValueType::EndOffset(ofs) => {
+                builder.end_offset(timing.frame(ofs as usize))
+            }
             &sprite_studio::ValueType::Infinity(inf) => builder.infinity(inf),
             &sprite_studio::ValueType::Reverse(rev) => builder.reverse(rev),
             &sprite_studio::ValueType::PingPong(ping) => builder.pingpong(ping),
@@ -419,19 +1082,88 @@ fn convert_instance_key(key_values: &sprite_studio::KeyValue) -> InstanceKey {
         .build()
 }
 
+// ユーザーデータは数値・矩形・座標・文字列を同時に持てる
+// どの値が設定されていても読み方は UserDataMode だけで決まる
 fn convert_user<U: serde::de::DeserializeOwned>(
     key_values: &sprite_studio::KeyValue,
+    mode: UserDataMode,
 ) -> Result<U, ParseAnimationError> {
-    let text = key_values
+    use serde_json::{json, Map, Value};
+
+    let mut user = Map::new();
+    let mut text = None;
+    for v in key_values.values() {
+        match v {
+            &sprite_studio::ValueType::Integer(integer) => {
+                user.insert("integer".into(), json!(integer));
+            }
+            &sprite_studio::ValueType::Rect(x, y, width, height) => {
+                user.insert(
+                    "rect".into(),
+                    json!({ "x": x, "y": y, "width": width, "height": height }),
+                );
+            }
+            &sprite_studio::ValueType::Point(x, y) => {
+                user.insert("point".into(), json!({ "x": x, "y": y }));
+            }
+            sprite_studio::ValueType::Text(t) => text = Some(t.clone()),
+            _ => {}
+        }
+    }
+
+    match mode {
+        UserDataMode::Json => {
+            let text = text.ok_or(ParseAnimationError::NotSetUserData)?;
+            serde_json::de::from_str(&text)
+                .map_err(|err| ParseAnimationError::JsonDeserializeError { err, source: text })
+        }
+        UserDataMode::Envelope => {
+            if let Some(text) = text {
+                user.insert("string".into(), Value::String(text));
+            }
+            if user.is_empty() {
+                return Err(ParseAnimationError::NotSetUserData);
+            }
+            let user = Value::Object(user);
+            let source = user.to_string();
+            serde_json::from_value(user)
+                .map_err(|err| ParseAnimationError::JsonDeserializeError { err, source })
+        }
+    }
+}
+
+// シグナルはゲーム側のイベント(足音や攻撃判定など)として使う
+// 無効にされているコマンドは出力しない
+fn convert_signal(key_values: &sprite_studio::KeyValue) -> Result<SignalKey, ParseAnimationError> {
+    let commands = key_values
         .values()
         .find_map(|v| match v {
-            sprite_studio::ValueType::Text(text) => Some(text.clone()),
+            sprite_studio::ValueType::Signal(commands) => Some(commands),
             _ => None,
         })
-        .ok_or(ParseAnimationError::NotSetText)?;
+        .ok_or(ParseAnimationError::NotSetSignal)?;
 
-    serde_json::de::from_str(&text)
-        .map_err(|err| ParseAnimationError::JsonDeserializeError { err, source: text })
+    let signal = commands
+        .iter()
+        .filter(|command| command.active())
+        .fold(SignalKeyBuilder::new(), |builder, command| {
+            let params = command
+                .params()
+                .map(|param| (param.id().to_string(), convert_signal_value(param.value())))
+                .collect();
+            builder.command(command.id(), params)
+        })
+        .build();
+    Ok(signal)
+}
+
+fn convert_signal_value(value: &sprite_studio::SignalValue) -> SignalValue {
+    match value {
+        &sprite_studio::SignalValue::Integer(val) => SignalValue::Integer(val),
+        &sprite_studio::SignalValue::Float(val) => SignalValue::Float(val),
+        &sprite_studio::SignalValue::Index(val) => SignalValue::Index(val),
+        sprite_studio::SignalValue::Text(val) => SignalValue::Text(val.clone()),
+    }
 }
 
 fn convert_vertex(key_values: &sprite_studio::KeyValue) -> Result<VertexKey, ParseAnimationError> {
@@ -448,6 +1180,32 @@ fn convert_vertex(key_values: &sprite_studio::KeyValue) -> Result<VertexKey, Par
     Ok(vertex)
 }
 
+// メッシュの頂点ごとのオフセット
+// 変化のない頂点はキーに含まれないので 0 で埋める
+fn convert_deform(
+    key_values: &sprite_studio::KeyValue,
+) -> Result<Vec<(f32, f32)>, ParseAnimationError> {
+    let size = key_values
+        .values()
+        .find_map(|v| match v {
+            &sprite_studio::ValueType::DeformSize(size) => Some(size as usize),
+            _ => None,
+        })
+        .ok_or(ParseAnimationError::NotSetDeform)?;
+
+    let mut offsets = vec![(0., 0.); size];
+    for v in key_values.values() {
+        if let &sprite_studio::ValueType::DeformVertex(index, x, y) = v {
+            let index = index as usize;
+            if index >= size {
+                return Err(ParseAnimationError::DeformIndexOutOfRange { index, size });
+            }
+            offsets[index] = (x, y);
+        }
+    }
+    Ok(offsets)
+}
+
 fn convert_effect(key_values: &sprite_studio::KeyValue) -> Result<EffectKey, ParseAnimationError> {
     let effect = key_values
         .values()
@@ -460,3 +1218,21 @@ fn convert_effect(key_values: &sprite_studio::KeyValue) -> Result<EffectKey, Par
         .build();
     Ok(effect)
 }
+
+fn convert_effect_play_key(key_values: &sprite_studio::KeyValue, frame: usize) -> EffectPlayKey {
+    key_values.values().fold(
+        EffectPlayKey {
+            frame,
+            start_time: 0.,
+            speed: 1.,
+        },
+        |mut key, v| {
+            match v {
+                &sprite_studio::ValueType::StartTime(time) => key.start_time = time as f32,
+                &sprite_studio::ValueType::Speed(speed) => key.speed = speed as f32,
+                _ => {}
+            }
+            key
+        },
+    )
+}
@@ -0,0 +1,148 @@
+use crate::error::ParseAnimationError;
+
+// FPSを変換するときにキーの時間どおりの値を再現するため、元のFPSのままカーブを評価する
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Curve {
+    Linear,
+    Step,
+    Acceleration,
+    Deceleration,
+    // 始点と終点の接線
+    Hermite(f32, f32),
+    // 始点側はこのキー、終点側は次のキーからの相対(時間, 値)の制御点
+    Bezier((f32, f32), (f32, f32)),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CurveKey {
+    // 元のFPSでのフレーム
+    pub(crate) time: f32,
+    pub(crate) value: f32,
+    pub(crate) curve: Curve,
+}
+
+impl CurveKey {
+    pub(crate) fn from_key(
+        key: &sprite_studio::KeyValue,
+        value: f32,
+    ) -> Result<Self, ParseAnimationError> {
+        use sprite_studio::Interpolation as SsInter;
+        let curve = match key.interpolation() {
+            SsInter::Linear => Curve::Linear,
+            SsInter::Step => Curve::Step,
+            SsInter::Acceleration => Curve::Acceleration,
+            SsInter::Deceleration => Curve::Deceleration,
+            SsInter::Hermite => {
+                let (_, start, _, end) = key.curve().ok_or(ParseAnimationError::NotSetCurve)?;
+                Curve::Hermite(start, end)
+            }
+            SsInter::Bezier => {
+                let (start_time, start_value, end_time, end_value) =
+                    key.curve().ok_or(ParseAnimationError::NotSetCurve)?;
+                Curve::Bezier((start_time, start_value), (end_time, end_value))
+            }
+        };
+        Ok(CurveKey {
+            time: key.time() as f32,
+            value,
+            curve,
+        })
+    }
+}
+
+// 最初のキーより前は最初の値、最後のキーより後は最後の値
+// keys は時間順
+pub(crate) fn sample(keys: &[CurveKey], time: f32) -> f32 {
+    match keys.iter().position(|key| key.time > time) {
+        Some(0) => keys[0].value,
+        Some(next) => interpolate(&keys[next - 1], &keys[next], time),
+        None => keys.last().map(|key| key.value).unwrap_or(0.),
+    }
+}
+
+// 次のキーまで値が変わらない区間か
+pub(crate) fn holds(keys: &[CurveKey], time: f32) -> bool {
+    match keys.iter().rev().find(|key| key.time <= time) {
+        Some(key) => match key.curve {
+            Curve::Step => true,
+            _ => keys.last().map(|last| last.time <= time).unwrap_or(true),
+        },
+        None => true,
+    }
+}
+
+fn interpolate(key: &CurveKey, next: &CurveKey, time: f32) -> f32 {
+    let t = (time - key.time) / (next.time - key.time);
+    let (from, to) = (key.value, next.value);
+    let rate = match key.curve {
+        Curve::Linear => t,
+        Curve::Step => 0.,
+        Curve::Acceleration => t * t,
+        Curve::Deceleration => 1. - (1. - t) * (1. - t),
+        Curve::Hermite(start, end) => {
+            let (t2, t3) = (t * t, t * t * t);
+            return (2. * t3 - 3. * t2 + 1.) * from
+                + (-2. * t3 + 3. * t2) * to
+                + (t3 - 2. * t2 + t) * start
+                + (t3 - t2) * end;
+        }
+        Curve::Bezier(start, end) => {
+            let xs = [key.time, key.time + start.0, next.time + end.0, next.time];
+            let ys = [from, from + start.1, to + end.1, to];
+            return cubic(&ys, solve_bezier_time(&xs, time));
+        }
+    };
+    from + (to - from) * rate
+}
+
+fn cubic(points: &[f32; 4], u: f32) -> f32 {
+    let v = 1. - u;
+    v * v * v * points[0]
+        + 3. * v * v * u * points[1]
+        + 3. * v * u * u * points[2]
+        + u * u * u * points[3]
+}
+
+// 時間軸は単調増加とみなして二分探索で媒介変数を求める
+fn solve_bezier_time(xs: &[f32; 4], time: f32) -> f32 {
+    let (mut low, mut high) = (0., 1.);
+    for _ in 0..24 {
+        let mid = (low + high) / 2.;
+        if cubic(xs, mid) < time {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(time: f32, value: f32, curve: Curve) -> CurveKey {
+        CurveKey { time, value, curve }
+    }
+
+    #[test]
+    fn sample_between_keys() {
+        let keys = [key(0., 0., Curve::Linear), key(10., 100., Curve::Step)];
+        assert_eq!(sample(&keys, -1.), 0.);
+        assert_eq!(sample(&keys, 2.5), 25.);
+        assert_eq!(sample(&keys, 12.), 100.);
+
+        let keys = [key(0., 0., Curve::Step), key(10., 100., Curve::Linear)];
+        assert_eq!(sample(&keys, 9.9), 0.);
+        assert!(holds(&keys, 5.));
+    }
+
+    #[test]
+    fn bezier_with_straight_handles_is_linear() {
+        let curve = Curve::Bezier((10. / 3., 100. / 3.), (-10. / 3., -100. / 3.));
+        let keys = [key(0., 0., curve), key(10., 100., Curve::Linear)];
+        for &time in &[1., 2.5, 5., 7.5] {
+            assert!((sample(&keys, time) - time * 10.).abs() < 0.01);
+        }
+    }
+}
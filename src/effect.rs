@@ -0,0 +1,276 @@
+use crate::{
+    cell_map::CellMapTable,
+    error::{ConvertError, Location, ParseAnimationError},
+    report::Reporter,
+};
+use serde::{Deserialize, Serialize};
+
+// エフェクトの出力データ
+// 並び順は project.effects() の順番で、パーツの refference_effect_index と一致する
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Effect {
+    pub name: String,
+    pub fps: u32,
+    // 乱数の種を固定している場合のみ
+    pub seed: Option<u32>,
+    pub layout_scale: (f32, f32),
+    pub emitters: Vec<Emitter>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Emitter {
+    pub name: String,
+    // パーティクルから発生する子エミッターの場合は親エミッターの emitters 内のインデックス
+    pub parent: Option<usize>,
+    // (全体のセルマップID, セルID)
+    pub cell: Option<(usize, usize)>,
+    pub blend: EffectBlend,
+    pub basic: EmitterBasic,
+    pub behaviors: Vec<ParticleBehavior>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum EffectBlend {
+    Mix,
+    Add,
+}
+
+// エミッターの基本設定
+// 範囲のある値は (最小, 最大)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmitterBasic {
+    pub priority: f32,
+    pub max_particles: usize,
+    // 一度に発生させる数と発生間隔(フレーム)
+    pub create_count: usize,
+    pub interval: usize,
+    // エミッターの寿命(フレーム)
+    pub lifetime: usize,
+    pub speed: (f32, f32),
+    // パーティクルの寿命(フレーム)
+    pub lifespan: (f32, f32),
+    // 放出方向(度)とその幅
+    pub angle: f32,
+    pub angle_variance: f32,
+}
+
+// パーティクルに掛かる効果
+// init は発生時、trans は寿命の終わりに向けて変化させる値
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ParticleBehavior {
+    Delay(usize),
+    OverwriteSeed(u32),
+    Gravity(f32, f32),
+    InitPosition {
+        x: (f32, f32),
+        y: (f32, f32),
+    },
+    InitRotation {
+        rotation: (f32, f32),
+        speed: (f32, f32),
+    },
+    TransRotation {
+        factor: f32,
+        end_life_rate: f32,
+    },
+    TransSpeed((f32, f32)),
+    TangentialAcceleration((f32, f32)),
+    PointGravity {
+        position: (f32, f32),
+        power: f32,
+    },
+    InitSize {
+        x: (f32, f32),
+        y: (f32, f32),
+        scale: (f32, f32),
+    },
+    TransSize {
+        x: (f32, f32),
+        y: (f32, f32),
+        scale: (f32, f32),
+    },
+    // 色は (r, g, b, a) の範囲
+    InitColor([f32; 4], [f32; 4]),
+    TransColor([f32; 4], [f32; 4]),
+    // 寿命に対するフェードイン・フェードアウトの割合
+    AlphaFade(f32, f32),
+}
+
+// 収集モードではエラーのあったエフェクトを飛ばして続ける
+pub(crate) fn convert_effects(
+    project: &sprite_studio::SpriteStudioData,
+    cell_maps: &CellMapTable,
+    reporter: &mut Reporter,
+) -> Result<Vec<Effect>, ConvertError> {
+    let mut effects = vec![];
+    for effect in project.effects() {
+        match convert_effect(effect, cell_maps, reporter) {
+            Ok(effect) => effects.push(effect),
+            Err(err) => reporter.error(err)?,
+        }
+    }
+    Ok(effects)
+}
+
+fn convert_effect(
+    effect: &sprite_studio::Effect,
+    cell_maps: &CellMapTable,
+    reporter: &mut Reporter,
+) -> Result<Effect, ConvertError> {
+    let location = Location::default().effect(effect.name());
+
+    // ルートノードは出力しないので、ノード番号とエミッター番号の対応を取っておく
+    let emitter_nodes = effect
+        .nodes()
+        .filter(|node| match node.node_type() {
+            sprite_studio::EffectNodeType::Emitter => true,
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+
+    let mut emitters = vec![];
+    for node in &emitter_nodes {
+        let location = location.emitter(node.name());
+
+        // 親がパーティクルノードならその親のエミッターから発生する
+        let parent = effect
+            .nodes()
+            .nth(node.parent_index() as usize)
+            .filter(|parent| match parent.node_type() {
+                sprite_studio::EffectNodeType::Particle => true,
+                _ => false,
+            })
+            .and_then(|particle| {
+                emitter_nodes
+                    .iter()
+                    .position(|emitter| emitter.index() == particle.parent_index())
+            });
+
+        let cell = match (node.cell_map_name(), node.cell_name()) {
+            (Some(map_name), Some(cell_name)) => Some(
+                cell_maps
+                    .resolve_by_name(map_name, cell_name)
+                    .map_err(|err| location.error(err))?,
+            ),
+            _ => None,
+        };
+
+        let blend = match node.blend_type() {
+            sprite_studio::BlendType::Add => EffectBlend::Add,
+            sprite_studio::BlendType::Mix => EffectBlend::Mix,
+            blend => {
+                reporter.warn(
+                    &location,
+                    format!("effect blend {:?} is not supported, use mix", blend),
+                );
+                EffectBlend::Mix
+            }
+        };
+
+        let mut basic = None;
+        let mut behaviors = vec![];
+        for behavior in node.behaviors() {
+            use sprite_studio::EffectBehavior as SsBehavior;
+            let behavior = match behavior {
+                SsBehavior::Basic {
+                    priority,
+                    maximum_particle,
+                    at_time_create,
+                    interval,
+                    lifetime,
+                    speed,
+                    lifespan,
+                    angle,
+                    angle_variance,
+                } => {
+                    basic = Some(EmitterBasic {
+                        priority: *priority,
+                        max_particles: *maximum_particle as usize,
+                        create_count: *at_time_create as usize,
+                        interval: *interval as usize,
+                        lifetime: *lifetime as usize,
+                        speed: *speed,
+                        lifespan: *lifespan,
+                        angle: *angle,
+                        angle_variance: *angle_variance,
+                    });
+                    continue;
+                }
+                SsBehavior::Delay(frame) => ParticleBehavior::Delay(*frame as usize),
+                SsBehavior::OverWriteSeed(seed) => ParticleBehavior::OverwriteSeed(*seed),
+                SsBehavior::Gravity(x, y) => ParticleBehavior::Gravity(*x, *y),
+                SsBehavior::InitPosition { x, y } => {
+                    ParticleBehavior::InitPosition { x: *x, y: *y }
+                }
+                SsBehavior::InitRotation {
+                    rotation,
+                    rotation_add,
+                } => ParticleBehavior::InitRotation {
+                    rotation: *rotation,
+                    speed: *rotation_add,
+                },
+                SsBehavior::TransRotation {
+                    factor,
+                    end_life_time_per,
+                } => ParticleBehavior::TransRotation {
+                    factor: *factor,
+                    end_life_rate: *end_life_time_per,
+                },
+                SsBehavior::TransSpeed(speed) => ParticleBehavior::TransSpeed(*speed),
+                SsBehavior::TangentialAcceleration(acceleration) => {
+                    ParticleBehavior::TangentialAcceleration(*acceleration)
+                }
+                SsBehavior::PointGravity { position, power } => ParticleBehavior::PointGravity {
+                    position: *position,
+                    power: *power,
+                },
+                SsBehavior::InitSize { x, y, scale } => ParticleBehavior::InitSize {
+                    x: *x,
+                    y: *y,
+                    scale: *scale,
+                },
+                SsBehavior::TransSize { x, y, scale } => ParticleBehavior::TransSize {
+                    x: *x,
+                    y: *y,
+                    scale: *scale,
+                },
+                SsBehavior::InitColor(min, max) => ParticleBehavior::InitColor(*min, *max),
+                SsBehavior::TransColor(min, max) => ParticleBehavior::TransColor(*min, *max),
+                SsBehavior::AlphaFade(fade_in, fade_out) => {
+                    ParticleBehavior::AlphaFade(*fade_in, *fade_out)
+                }
+                behavior => {
+                    reporter.warn(
+                        &location,
+                        format!("effect behavior {:?} is not supported", behavior),
+                    );
+                    continue;
+                }
+            };
+            behaviors.push(behavior);
+        }
+
+        // 基本設定のないエミッターは何も発生させられない
+        let basic = match basic {
+            Some(basic) => basic,
+            None => return Err(location.error(ParseAnimationError::NotSetEmitterBasic)),
+        };
+
+        emitters.push(Emitter {
+            name: node.name().into(),
+            parent,
+            cell,
+            blend,
+            basic,
+            behaviors,
+        });
+    }
+
+    Ok(Effect {
+        name: effect.name().into(),
+        fps: effect.fps(),
+        seed: effect.seed(),
+        layout_scale: effect.layout_scale(),
+        emitters,
+    })
+}
@@ -16,14 +16,240 @@ pub enum ParseAnimationError {
     NotSetMapId,
     #[fail(display = "cell name value not set")]
     NotSetCellName,
+    #[fail(display = "unknown cell map: {}", name)]
+    UnknownCellMap { name: String },
+    #[fail(
+        display = "cell map id {} out of range (pack has {} cell maps)",
+        map_id, count
+    )]
+    CellMapIndexOutOfRange { map_id: usize, count: usize },
+    #[fail(display = "cell \"{}\" not found in cell map {}", name, map)]
+    UnknownCellName { map: String, name: String },
     #[fail(display = "color value not set")]
     NotSetColor,
-    #[fail(display = "text value not set")]
-    NotSetText,
+    #[fail(display = "vertex color needs 4 colors but {} set", count)]
+    NotSetVertexColor { count: usize },
+    #[fail(display = "user data value not set")]
+    NotSetUserData,
+    #[fail(display = "signal value not set")]
+    NotSetSignal,
+    #[fail(display = "curve value not set")]
+    NotSetCurve,
+    #[fail(display = "deform value not set")]
+    NotSetDeform,
+    #[fail(
+        display = "deform vertex {} out of range (mesh has {} vertices)",
+        index, size
+    )]
+    DeformIndexOutOfRange { index: usize, size: usize },
     #[fail(display = "json deserialize error: {}, source: {}", err, source)]
     JsonDeserializeError { err: JsonError, source: String },
     #[fail(display = "conflict position z type. please use position z or priority")]
     ConflictPositionZ,
+    #[fail(
+        display = "cannot bake {:?} into scale and flip keys already animated on the part",
+        attribute
+    )]
+    ConflictRotationBake { attribute: AttributeTag },
+    #[fail(display = "emitter has no basic behavior")]
+    NotSetEmitterBasic,
+    #[fail(display = "mesh bind refers to unknown bone part {}", index)]
+    UnknownBone { index: i32 },
     #[fail(display = "unsupported attribute: {:?}", attribute)]
     NonSupportedAttribute { attribute: AttributeTag },
+    #[fail(
+        display = "cell \"{}\" in cell map {} ({}x{}) does not fit in atlas page size {}",
+        name, map, width, height, max_size
+    )]
+    AtlasCellTooLarge {
+        map: String,
+        name: String,
+        width: u32,
+        height: u32,
+        max_size: u32,
+    },
+    #[fail(display = "cells do not fit in {} atlas pages", max_pages)]
+    AtlasPageOverflow { max_pages: usize },
+    #[fail(display = "trimmed atlas layout needs the cell map images")]
+    TrimmedAtlasNeedsImages,
+}
+
+impl ParseAnimationError {
+    // 集計用のエラーの種類名
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ParseAnimationError::PartIndexError(..) => "PartIndexError",
+            ParseAnimationError::NonSupportedFps { .. } => "NonSupportedFps",
+            ParseAnimationError::NotSetFloatValue => "NotSetFloatValue",
+            ParseAnimationError::NotSetBoolValue => "NotSetBoolValue",
+            ParseAnimationError::NotSetMapId => "NotSetMapId",
+            ParseAnimationError::NotSetCellName => "NotSetCellName",
+            ParseAnimationError::UnknownCellMap { .. } => "UnknownCellMap",
+            ParseAnimationError::CellMapIndexOutOfRange { .. } => "CellMapIndexOutOfRange",
+            ParseAnimationError::UnknownCellName { .. } => "UnknownCellName",
+            ParseAnimationError::NotSetColor => "NotSetColor",
+            ParseAnimationError::NotSetVertexColor { .. } => "NotSetVertexColor",
+            ParseAnimationError::NotSetUserData => "NotSetUserData",
+            ParseAnimationError::NotSetSignal => "NotSetSignal",
+            ParseAnimationError::NotSetCurve => "NotSetCurve",
+            ParseAnimationError::NotSetDeform => "NotSetDeform",
+            ParseAnimationError::DeformIndexOutOfRange { .. } => "DeformIndexOutOfRange",
+            ParseAnimationError::JsonDeserializeError { .. } => "JsonDeserializeError",
+            ParseAnimationError::ConflictPositionZ => "ConflictPositionZ",
+            ParseAnimationError::ConflictRotationBake { .. } => "ConflictRotationBake",
+            ParseAnimationError::NotSetEmitterBasic => "NotSetEmitterBasic",
+            ParseAnimationError::UnknownBone { .. } => "UnknownBone",
+            ParseAnimationError::NonSupportedAttribute { .. } => "NonSupportedAttribute",
+            ParseAnimationError::AtlasCellTooLarge { .. } => "AtlasCellTooLarge",
+            ParseAnimationError::AtlasPageOverflow { .. } => "AtlasPageOverflow",
+            ParseAnimationError::TrimmedAtlasNeedsImages => "TrimmedAtlasNeedsImages",
+        }
+    }
+}
+
+// エラーの発生箇所
+// 変換処理を潜るたびに情報を足していく
+#[derive(Debug, Clone, Default)]
+pub struct Location {
+    pub project: Option<String>,
+    pub pack: Option<String>,
+    pub animation: Option<String>,
+    pub part: Option<String>,
+    pub effect: Option<String>,
+    pub emitter: Option<String>,
+    pub attribute: Option<AttributeTag>,
+    pub frame: Option<usize>,
+}
+
+impl Location {
+    pub fn project<S: Into<String>>(&self, project: S) -> Self {
+        Location {
+            project: Some(project.into()),
+            ..self.clone()
+        }
+    }
+
+    pub fn pack<S: Into<String>>(&self, pack: S) -> Self {
+        Location {
+            pack: Some(pack.into()),
+            ..self.clone()
+        }
+    }
+
+    pub fn animation<S: Into<String>>(&self, animation: S) -> Self {
+        Location {
+            animation: Some(animation.into()),
+            ..self.clone()
+        }
+    }
+
+    pub fn part<S: Into<String>>(&self, part: S) -> Self {
+        Location {
+            part: Some(part.into()),
+            ..self.clone()
+        }
+    }
+
+    pub fn effect<S: Into<String>>(&self, effect: S) -> Self {
+        Location {
+            effect: Some(effect.into()),
+            ..self.clone()
+        }
+    }
+
+    pub fn emitter<S: Into<String>>(&self, emitter: S) -> Self {
+        Location {
+            emitter: Some(emitter.into()),
+            ..self.clone()
+        }
+    }
+
+    pub fn attribute(&self, attribute: AttributeTag) -> Self {
+        Location {
+            attribute: Some(attribute),
+            ..self.clone()
+        }
+    }
+
+    pub fn frame(&self, frame: usize) -> Self {
+        Location {
+            frame: Some(frame),
+            ..self.clone()
+        }
+    }
+
+    pub fn error<E: Into<failure::Error>>(&self, error: E) -> ConvertError {
+        ConvertError {
+            location: self.clone(),
+            error: error.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut chain = vec![];
+        if let Some(project) = &self.project {
+            chain.push(format!("project \"{}\"", project));
+        }
+        if let Some(pack) = &self.pack {
+            chain.push(format!("pack \"{}\"", pack));
+        }
+        if let Some(animation) = &self.animation {
+            chain.push(format!("animation \"{}\"", animation));
+        }
+        if let Some(part) = &self.part {
+            chain.push(format!("part \"{}\"", part));
+        }
+        if let Some(effect) = &self.effect {
+            chain.push(format!("effect \"{}\"", effect));
+        }
+        if let Some(emitter) = &self.emitter {
+            chain.push(format!("emitter \"{}\"", emitter));
+        }
+        if let Some(attribute) = &self.attribute {
+            chain.push(format!("attribute {:?}", attribute));
+        }
+        if let Some(frame) = &self.frame {
+            chain.push(format!("frame {}", frame));
+        }
+        if chain.is_empty() {
+            write!(f, "<unknown>")
+        } else {
+            write!(f, "{}", chain.join(" > "))
+        }
+    }
+}
+
+// 発生箇所付きのエラー
+#[derive(Debug)]
+pub struct ConvertError {
+    pub location: Location,
+    pub error: failure::Error,
+}
+
+impl ConvertError {
+    // 変換時のエラーにプロジェクトファイルの情報を付け足す
+    pub fn in_project<S: Into<String>>(error: failure::Error, project: S) -> Self {
+        match error.downcast::<ConvertError>() {
+            Ok(mut error) => {
+                error.location.project = Some(project.into());
+                error
+            }
+            Err(error) => Location::default().project(project).error(error),
+        }
+    }
+}
+
+impl std::fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}\n    at {}", self.error, self.location)
+    }
+}
+
+impl Fail for ConvertError {
+    // 元のエラーのメッセージは表示に含めているので、その原因から辿らせる
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.error.as_fail().cause()
+    }
 }
@@ -1,14 +1,28 @@
+mod atlas;
+mod cell_map;
 pub mod convert;
-mod error;
+mod curve;
+pub mod effect;
+pub mod error;
+pub mod manifest;
+pub mod mesh;
+pub mod options;
+mod particle;
+pub mod report;
+mod rotation;
+pub mod skeleton;
 mod sprite_sheet;
+pub mod string_key;
+pub mod trim;
 
 use amethyst_sprite_studio::traits::animation_file::AnimationFile;
+use image::RgbaImage;
 use log::*;
+use options::ConvertOptions;
 use ron::ser::*;
 use serde::Serialize;
-use sprite_studio::{load_project, AnimationCells};
+use sprite_studio::load_project;
 use std::{
-    collections::BTreeMap,
     io::{BufWriter, Write},
     path::Path,
     str::FromStr,
@@ -18,6 +32,22 @@ pub fn convert_to_timeline<F, T>(
     dir_path: F,
     project_path: F,
 ) -> std::result::Result<(), failure::Error>
+where
+    F: AsRef<std::path::Path>,
+    T: AnimationFile,
+    T::PackKey: FromStr,
+    T::AnimationKey: FromStr,
+    <T::PackKey as FromStr>::Err: failure::Fail,
+    <T::AnimationKey as FromStr>::Err: failure::Fail,
+{
+    convert_to_timeline_with_options::<F, T>(dir_path, project_path, &ConvertOptions::default())
+}
+
+pub fn convert_to_timeline_with_options<F, T>(
+    dir_path: F,
+    project_path: F,
+    options: &ConvertOptions,
+) -> std::result::Result<(), failure::Error>
 where
     F: AsRef<std::path::Path>,
     T: AnimationFile,
@@ -27,7 +57,12 @@ where
     <T::AnimationKey as FromStr>::Err: failure::Fail,
 {
     let project_data = load_project(project_path.as_ref())?;
-    convert_to_sprite_animation::<T>(&project_data, project_path.as_ref(), dir_path.as_ref())?;
+    convert_to_sprite_animation::<T>(
+        &project_data,
+        project_path.as_ref(),
+        dir_path.as_ref(),
+        options,
+    )?;
     Ok(())
 }
 
@@ -35,6 +70,7 @@ fn convert_to_sprite_animation<'a, T>(
     project_data: &'a sprite_studio::SpriteStudioData,
     project_path: &Path,
     output_dir: &Path,
+    options: &ConvertOptions,
 ) -> std::result::Result<(), failure::Error>
 where
     T: AnimationFile,
@@ -56,32 +92,187 @@ where
     std::fs::create_dir_all(&sheet_dir)?;
     std::fs::create_dir_all(&animation_dir)?;
 
-    let mut cell_name_dict = vec![];
+    let mut manifest = manifest::Manifest::new(&project_name.to_string_lossy());
+    // 切り詰めかアトラスの場合だけ画像を読み込む
+    let images = if options.trim_alpha || options.atlas.is_some() {
+        load_cell_map_images(project_data, project_dir)?
+    } else {
+        vec![]
+    };
+
+    // 切り詰める場合は画像の不透明な部分をセルの範囲にする
+    // 画像・シートもアニメーションのセルIDもこの配置から作る
+    let layout =
+        cell_map::make_cell_layout(project_data, Some(&images), options).map_err(|err| {
+            error::ConvertError::in_project(err.into(), project_path.display().to_string())
+        })?;
+    if let Some(summary) = &layout.trim {
+        // アトラスにまとめない場合は画像をそのままコピーするので、テクスチャは小さくならない
+        let saved = match options.atlas {
+            Some(_) => format!("{} px saved in atlas", summary.saved_area()),
+            None => "images are copied as is".to_string(),
+        };
+        info!(
+            "trim: {} cells trimmed, {} cells kept, cell area {} => {} px ({})",
+            summary.trimmed_cells,
+            summary.kept_cells,
+            summary.original_area,
+            summary.trimmed_area,
+            saved
+        );
+    }
+
+    let cell_maps = layout.cell_map_table(project_data);
+    match &layout.atlas {
+        Some(atlas_layout) => {
+            // アトラスにまとめる場合はページごとに画像とシートを出力する
+            write_atlas_pages(
+                project_data,
+                atlas_layout,
+                &images,
+                &layout.rects,
+                &image_dir,
+                &sheet_dir,
+                &mut manifest,
+            )?;
+        }
+        None => write_cell_maps(
+            project_data,
+            &layout.rects,
+            project_dir,
+            &image_dir,
+            &sheet_dir,
+            &mut manifest,
+        )?,
+    }
+    manifest.trim = layout.trim;
+
+    let anim = convert::convert_with_cell_maps::<T>(project_data, &cell_maps, options)
+        .map_err(|err| error::ConvertError::in_project(err, project_path.display().to_string()))?;
+    data_to_file(anim, animation_dir.join("animation.anim.ron"))?;
+
+    // エフェクトのエミッター定義はアニメーションの隣に出力する
+    let effects =
+        effect::convert_effects(project_data, &cell_maps, &mut report::Reporter::fail_fast())
+            .map_err(|err| {
+                error::ConvertError::in_project(err.into(), project_path.display().to_string())
+            })?;
+    if effects.is_empty() == false {
+        manifest.effects = effects.iter().map(|effect| effect.name.clone()).collect();
+        data_to_file(&effects, animation_dir.join("effect.effect.ron"))?;
+        manifest.effect = Some("animation/effect.effect.ron".into());
+    }
+
+    // ボーンのあるプロジェクトは階層とウェイトを別ファイルに出力する
+    let skeletons = skeleton::make_skeletons(project_data, &mut report::Reporter::fail_fast())
+        .map_err(|err| {
+            error::ConvertError::in_project(err.into(), project_path.display().to_string())
+        })?;
+    if skeletons.is_empty() == false {
+        data_to_file(skeletons, animation_dir.join("skeleton.ron"))?;
+        manifest.skeleton = Some("animation/skeleton.ron".into());
+    }
+
+    // 焼き込んだパーティクルのパーツもパックのパーツとして並べる
+    let baked_effects = if options.bake_effects {
+        &effects[..]
+    } else {
+        &[]
+    };
+    manifest.add_packs(project_data, options, baked_effects);
+    let json = std::fs::File::create(output_project_dir.join("manifest.json"))?;
+    serde_json::to_writer_pretty(BufWriter::new(json), &manifest)?;
+    data_to_file(manifest, output_project_dir.join("manifest.ron"))?;
+
+    Ok(())
+}
+
+fn write_cell_maps(
+    project_data: &sprite_studio::SpriteStudioData,
+    rects: &[Vec<trim::CellRect>],
+    project_dir: &Path,
+    image_dir: &Path,
+    sheet_dir: &Path,
+    manifest: &mut manifest::Manifest,
+) -> std::result::Result<(), failure::Error> {
     for (idx, cell_map) in project_data.cell_maps().enumerate() {
         // スプライトの分割情報を生成
         // ファイル生成時はIDのファイル名で生成する
+        // IDはアニメーション側の map_id と同じ project.cell_maps() の順番
         info!("{}: {} {:?}", idx, cell_map.name(), cell_map.image_path());
 
         // 画像を生成パス内にコピー
         let from = project_dir.join(cell_map.image_path());
-        let img_path = image_dir.join(format!("sprite{:03}.png", idx));
+        let img_path = image_dir.join(cell_map::image_file_name(idx));
 
         info!("{:?} => {:?}", from, img_path);
 
         std::fs::copy(from, img_path)?;
 
-        cell_name_dict.push(make_cell_name_dict(cell_map));
-        let sheet = sprite_sheet::make_sprite_sheet(cell_map);
-        let sheet_path = sheet_dir.join(format!("sprite{:03}.sheet.ron", idx));
+        manifest.add_cell_map(idx, cell_map);
+        let sheet = sprite_sheet::make_sprite_sheet(cell_map, &rects[idx]);
+        let sheet_path = sheet_dir.join(cell_map::sheet_file_name(idx));
         data_to_file(sheet, sheet_path)?;
+
+        // メッシュの頂点と三角形はシートの隣に出力する
+        if let Some(mesh_sheet) = mesh::make_mesh_sheet(cell_map) {
+            let mesh_path = sheet_dir.join(cell_map::mesh_file_name(idx));
+            data_to_file(mesh_sheet, mesh_path)?;
+            manifest.set_mesh(idx);
+        }
     }
+    Ok(())
+}
 
-    let anim = convert::convert::<T>(project_data)?;
-    data_to_file(anim, animation_dir.join("animation.anim.ron"))?;
+fn write_atlas_pages(
+    project_data: &sprite_studio::SpriteStudioData,
+    layout: &atlas::AtlasLayout,
+    images: &[RgbaImage],
+    rects: &[Vec<trim::CellRect>],
+    image_dir: &Path,
+    sheet_dir: &Path,
+    manifest: &mut manifest::Manifest,
+) -> std::result::Result<(), failure::Error> {
+    // 元のセルマップとページの対応を残す
+    for (idx, cell_map) in project_data.cell_maps().enumerate() {
+        manifest.add_source_cell_map(idx, cell_map);
+    }
 
+    for (idx, page) in layout.pages.iter().enumerate() {
+        // ページのIDがアニメーション側の map_id になる
+        info!("page {}: {}x{}", idx, page.width, page.height);
+
+        let img_path = image_dir.join(cell_map::image_file_name(idx));
+        info!("save: {:?}", img_path);
+        atlas::make_page_image(images, rects, page).save(img_path)?;
+
+        manifest.add_page(idx, project_data, page);
+        let sheet = atlas::make_sprite_sheet(project_data, rects, page);
+        data_to_file(sheet, sheet_dir.join(cell_map::sheet_file_name(idx)))?;
+
+        if let Some(mesh_sheet) = atlas::make_mesh_sheet(project_data, page) {
+            data_to_file(mesh_sheet, sheet_dir.join(cell_map::mesh_file_name(idx)))?;
+            manifest.set_page_mesh(idx);
+        }
+    }
     Ok(())
 }
 
+// セルマップの画像を project.cell_maps() の順番で読み込む
+pub fn load_cell_map_images(
+    project_data: &sprite_studio::SpriteStudioData,
+    project_dir: &Path,
+) -> std::result::Result<Vec<RgbaImage>, failure::Error> {
+    project_data
+        .cell_maps()
+        .map(|cell_map| {
+            let path = project_dir.join(cell_map.image_path());
+            info!("load: {:?}", path);
+            Ok(image::open(path)?.into_rgba())
+        })
+        .collect()
+}
+
 pub(crate) fn data_to_file<S, P>(data: S, path: P) -> std::result::Result<(), failure::Error>
 where
     S: Serialize,
@@ -101,13 +292,3 @@ where
     buff.write(string.as_bytes())?;
     Ok(())
 }
-
-fn make_cell_name_dict(cell_map: &AnimationCells) -> BTreeMap<String, usize> {
-    let mut cell_name_dict = BTreeMap::new();
-
-    for (idx, cell) in cell_map.cells().enumerate() {
-        cell_name_dict.insert(cell.name().into(), idx);
-    }
-
-    cell_name_dict
-}
@@ -0,0 +1,178 @@
+use sprite_studio::load_project;
+use sprite_studio_converter::{
+    convert, convert_to_timeline_with_options, load_cell_map_images, options::ConvertOptions,
+    string_key::StringKeyFile,
+};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "\
+usage:
+    sprite-studio-converter convert [options] <project.sspj> <output_dir>
+    sprite-studio-converter inspect <project.sspj>
+    sprite-studio-converter validate [options] <project.sspj>
+
+options:
+    --fps <fps>                       convert all animations to this fps (30 or 60)
+    --frame-mapping <nearest|exact>   how key times are mapped when converting fps
+    --rotation <track|bake>           keep x/y rotation keys or bake them into scale and flip
+    --user-data <json|envelope>       parse user data text as json or wrap all fields in an object
+    --bake-effects                    simulate effects and bake particles into part keys
+    --atlas <max_size>                repack all cells into texture pages of at most this size
+    --atlas-pages <count>             maximum number of atlas pages (default 8)
+    --trim                            shrink cells to their opaque pixels";
+
+// 終了コード
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+enum Command {
+    Convert {
+        project_path: PathBuf,
+        output_dir: PathBuf,
+    },
+    Inspect {
+        project_path: PathBuf,
+    },
+    Validate {
+        project_path: PathBuf,
+    },
+}
+
+// オプションと位置引数を分けて解釈する
+fn parse_args(args: &[String]) -> Option<(Command, ConvertOptions)> {
+    let mut options = ConvertOptions::default();
+    let mut positional = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fps" => options.target_fps = Some(args.next()?.parse().ok()?),
+            "--frame-mapping" => options.frame_mapping = args.next()?.parse().ok()?,
+            "--rotation" => options.rotation_mode = args.next()?.parse().ok()?,
+            "--user-data" => options.user_data = args.next()?.parse().ok()?,
+            "--bake-effects" => options.bake_effects = true,
+            "--atlas" => {
+                options.atlas.get_or_insert_with(Default::default).max_size =
+                    args.next()?.parse().ok()?
+            }
+            "--atlas-pages" => {
+                options.atlas.get_or_insert_with(Default::default).max_pages =
+                    args.next()?.parse().ok()?
+            }
+            "--trim" => options.trim_alpha = true,
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let command = match positional.as_slice() {
+        [command, project_path, output_dir] if command == "convert" => Some(Command::Convert {
+            project_path: project_path.into(),
+            output_dir: output_dir.into(),
+        }),
+        [command, project_path] if command == "inspect" => Some(Command::Inspect {
+            project_path: project_path.into(),
+        }),
+        [command, project_path] if command == "validate" => Some(Command::Validate {
+            project_path: project_path.into(),
+        }),
+        _ => None,
+    }?;
+    Some((command, options))
+}
+
+fn main() {
+    amethyst::start_logger(Default::default());
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let (command, options) = match parse_args(&args) {
+        Some(parsed) => parsed,
+        None => {
+            eprintln!("{}", USAGE);
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+
+    if let Err(err) = run(command, &options) {
+        eprintln!("error: {}", err);
+        for cause in err.iter_causes() {
+            eprintln!("  caused by: {}", cause);
+        }
+        std::process::exit(EXIT_FAILURE);
+    }
+}
+
+fn run(command: Command, options: &ConvertOptions) -> Result<(), failure::Error> {
+    match command {
+        Command::Convert {
+            project_path,
+            output_dir,
+        } => convert_to_timeline_with_options::<_, StringKeyFile>(
+            &output_dir,
+            &project_path,
+            options,
+        ),
+        Command::Inspect { project_path } => inspect(&project_path),
+        Command::Validate { project_path } => {
+            let project = load_project(&project_path)?;
+            // 最初のエラーで止めずに全て報告する
+            // 切り詰めたアトラスの配置は画像がないと決まらない
+            let mut report = if options.trim_alpha && options.atlas.is_some() {
+                let images = load_cell_map_images(&project, project_path.parent().unwrap())?;
+                convert::validate_with_images::<StringKeyFile>(&project, &images, options)
+            } else {
+                convert::validate::<StringKeyFile>(&project, options)
+            };
+            let project_name = project_path.display().to_string();
+            for error in &mut report.errors {
+                error.location.project = Some(project_name.clone());
+            }
+            for warning in &mut report.warnings {
+                warning.location.project = Some(project_name.clone());
+            }
+            println!("{}", report);
+            if report.is_ok() {
+                Ok(())
+            } else {
+                Err(failure::format_err!(
+                    "{:?}: {} errors found",
+                    project_path,
+                    report.errors.len()
+                ))
+            }
+        }
+    }
+}
+
+fn inspect(project_path: &Path) -> Result<(), failure::Error> {
+    let project = load_project(project_path)?;
+
+    println!("cell maps:");
+    for (idx, cell_map) in project.cell_maps().enumerate() {
+        println!(
+            "    sprite{:03}: {} {:?} ({} cells)",
+            idx,
+            cell_map.name(),
+            cell_map.image_path(),
+            cell_map.cells().count()
+        );
+    }
+
+    println!("packs:");
+    for pack in project.packs() {
+        println!("    {} ({} parts)", pack.name(), pack.parts().count());
+        for animation in pack.animations() {
+            println!(
+                "        {}: {} frames @ {} fps",
+                animation.name(),
+                animation.setting().count(),
+                animation.setting().fps()
+            );
+        }
+    }
+
+    println!("effects:");
+    for effect in project.effects() {
+        println!("    {}", effect.name());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,232 @@
+use crate::{
+    atlas::AtlasPage, cell_map, convert::FrameTiming, effect::Effect, options::ConvertOptions,
+    particle, trim::TrimSummary,
+};
+use serde::{Deserialize, Serialize};
+use sprite_studio::AnimationCells;
+use std::{collections::BTreeMap, path::Path};
+
+// 出力したファイルと元データの対応表
+// ランタイムやツールから名前で引けるように出力ディレクトリに保存する
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub project: String,
+    pub cell_maps: Vec<CellMapEntry>,
+    // アトラスにまとめた場合のみ
+    pub pages: Vec<PageEntry>,
+    pub packs: Vec<PackEntry>,
+    // ボーンがある場合のみ
+    pub skeleton: Option<String>,
+    // エフェクトがある場合のみ
+    // effects の並びはパーツの refference_effect_index と一致する
+    pub effect: Option<String>,
+    pub effects: Vec<String>,
+    // 透明な余白を切り詰めた場合のみ
+    pub trim: Option<TrimSummary>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CellMapEntry {
+    pub id: usize,
+    pub name: String,
+    pub file_name: String,
+    pub source_image: String,
+    // 出力ディレクトリからの相対パス
+    // アトラスにまとめた場合はセルマップごとには出力しないので None
+    pub image: Option<String>,
+    pub sheet: Option<String>,
+    // メッシュのセルがある場合のみ
+    pub mesh: Option<String>,
+    pub cells: BTreeMap<String, usize>,
+    // アトラスにまとめた場合にセルを置いたページのID
+    pub pages: Vec<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PageEntry {
+    pub id: usize,
+    pub image: String,
+    pub sheet: String,
+    pub mesh: Option<String>,
+    // ページにセルを置いた元のセルマップ
+    pub sources: Vec<PageSource>,
+    // 元のセルマップ名ごとの、セル名からページ内のセルIDへの対応
+    pub cells: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PageSource {
+    // cell_maps の id
+    pub cell_map: usize,
+    pub file_name: String,
+    pub source_image: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackEntry {
+    pub name: String,
+    pub parts: Vec<String>,
+    pub animations: Vec<AnimationEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnimationEntry {
+    pub name: String,
+    pub frame_count: usize,
+    pub fps: u32,
+}
+
+impl Manifest {
+    pub(crate) fn new(project_name: &str) -> Self {
+        Manifest {
+            project: project_name.into(),
+            cell_maps: vec![],
+            pages: vec![],
+            packs: vec![],
+            skeleton: None,
+            effect: None,
+            effects: vec![],
+            trim: None,
+        }
+    }
+
+    pub(crate) fn add_cell_map(&mut self, id: usize, cell_map: &AnimationCells) {
+        self.add_source_cell_map(id, cell_map);
+        if let Some(entry) = self.cell_maps.last_mut() {
+            entry.image = Some(format!("image/{}", cell_map::image_file_name(id)));
+            entry.sheet = Some(format!("sheet/{}", cell_map::sheet_file_name(id)));
+        }
+    }
+
+    // アトラスにまとめる場合も元のセルマップは残しておく
+    pub(crate) fn add_source_cell_map(&mut self, id: usize, cell_map: &AnimationCells) {
+        self.cell_maps.push(CellMapEntry {
+            id,
+            name: cell_map.name().into(),
+            file_name: cell_map.file_name().into(),
+            source_image: source_image(cell_map),
+            image: None,
+            sheet: None,
+            mesh: None,
+            cells: make_cell_name_dict(cell_map),
+            pages: vec![],
+        });
+    }
+
+    pub(crate) fn set_mesh(&mut self, id: usize) {
+        if let Some(entry) = self.cell_maps.iter_mut().find(|entry| entry.id == id) {
+            entry.mesh = Some(format!("sheet/{}", cell_map::mesh_file_name(id)));
+        }
+    }
+
+    pub(crate) fn add_page(
+        &mut self,
+        id: usize,
+        project: &sprite_studio::SpriteStudioData,
+        page: &AtlasPage,
+    ) {
+        let cell_maps = project.cell_maps().collect::<Vec<_>>();
+        let mut cells = BTreeMap::new();
+        let mut sources: Vec<PageSource> = vec![];
+        for (sprite_id, placed) in page.cells.iter().enumerate() {
+            let cell_map = cell_maps[placed.map_id];
+            let cell = cell_map.cells().nth(placed.cell_id).unwrap();
+            cells
+                .entry(cell_map.name().to_string())
+                .or_insert_with(BTreeMap::new)
+                .insert(cell.name().to_string(), sprite_id);
+
+            if sources
+                .iter()
+                .all(|source| source.cell_map != placed.map_id)
+            {
+                sources.push(PageSource {
+                    cell_map: placed.map_id,
+                    file_name: cell_map.file_name().into(),
+                    source_image: source_image(cell_map),
+                });
+            }
+        }
+        sources.sort_by_key(|source| source.cell_map);
+
+        for source in &sources {
+            if let Some(entry) = self
+                .cell_maps
+                .iter_mut()
+                .find(|entry| entry.id == source.cell_map)
+            {
+                entry.pages.push(id);
+            }
+        }
+
+        self.pages.push(PageEntry {
+            id,
+            image: format!("image/{}", cell_map::image_file_name(id)),
+            sheet: format!("sheet/{}", cell_map::sheet_file_name(id)),
+            mesh: None,
+            sources,
+            cells,
+        });
+    }
+
+    pub(crate) fn set_page_mesh(&mut self, id: usize) {
+        if let Some(entry) = self.pages.iter_mut().find(|entry| entry.id == id) {
+            entry.mesh = Some(format!("sheet/{}", cell_map::mesh_file_name(id)));
+        }
+    }
+
+    // フレーム数とFPSは変換後のアニメーションと同じ値にする
+    // パーツはエフェクトを焼き込んだパーティクルのパーツも含めて出力と同じ並びにする
+    pub(crate) fn add_packs(
+        &mut self,
+        project: &sprite_studio::SpriteStudioData,
+        options: &ConvertOptions,
+        baked_effects: &[Effect],
+    ) {
+        for pack in project.packs() {
+            let animations = pack
+                .animations()
+                .map(|animation| {
+                    let timing = FrameTiming::new(animation.setting().fps(), options);
+                    AnimationEntry {
+                        name: animation.name().into(),
+                        frame_count: timing.frame_count(animation.setting().count() as usize),
+                        fps: timing.target_fps,
+                    }
+                })
+                .collect();
+
+            let mut parts = pack
+                .parts()
+                .map(|part| part.name().to_string())
+                .collect::<Vec<_>>();
+            for (part_id, effect) in particle::baked_effect_parts(pack, baked_effects) {
+                let part_name = parts[part_id].clone();
+                parts.extend(
+                    (0..particle::slot_count(effect))
+                        .map(|slot| particle::slot_part_name(&part_name, slot)),
+                );
+            }
+
+            self.packs.push(PackEntry {
+                name: pack.name().into(),
+                parts,
+                animations,
+            });
+        }
+    }
+}
+
+fn source_image(cell_map: &AnimationCells) -> String {
+    Path::new(cell_map.image_path()).display().to_string()
+}
+
+fn make_cell_name_dict(cell_map: &AnimationCells) -> BTreeMap<String, usize> {
+    let mut cell_name_dict = BTreeMap::new();
+
+    for (idx, cell) in cell_map.cells().enumerate() {
+        cell_name_dict.insert(cell.name().into(), idx);
+    }
+
+    cell_name_dict
+}
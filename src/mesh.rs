@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use sprite_studio::AnimationCells;
+
+// メッシュパーツ用のセルの分割情報
+// シートと同じセルIDで引けるように、メッシュでないセルは None を入れる
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MeshSheet {
+    pub meshes: Vec<Option<MeshTopology>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MeshTopology {
+    // セルの左上を原点としたピクセル座標
+    pub points: Vec<[f32; 2]>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+// メッシュのセルが1つもなければ出力しない
+pub(crate) fn make_mesh_sheet(cell_map: &AnimationCells) -> Option<MeshSheet> {
+    let meshes = cell_map.cells().map(make_mesh_topology).collect::<Vec<_>>();
+
+    if meshes.iter().any(Option::is_some) {
+        Some(MeshSheet { meshes })
+    } else {
+        None
+    }
+}
+
+pub(crate) fn make_mesh_topology(cell: &sprite_studio::Cell) -> Option<MeshTopology> {
+    cell.mesh().map(|mesh| MeshTopology {
+        points: mesh.points().map(|(x, y)| [x, y]).collect(),
+        triangles: mesh.triangles().map(|(a, b, c)| [a, b, c]).collect(),
+    })
+}
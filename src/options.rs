@@ -0,0 +1,122 @@
+// 変換時の設定
+#[derive(Debug, Clone, Default)]
+pub struct ConvertOptions {
+    // 指定した場合は全てのアニメーションをこのFPSに変換する
+    // 指定しない場合は対応していないFPSのアニメーションはエラー
+    pub target_fps: Option<u32>,
+    pub frame_mapping: FrameMapping,
+    pub rotation_mode: RotationMode,
+    pub user_data: UserDataMode,
+    // パーティクルを再生できないランタイム向けにエフェクトをパーツのキーに焼き込む
+    pub bake_effects: bool,
+    // 指定した場合はセルマップの画像をまとめ直したページで出力する
+    pub atlas: Option<AtlasOptions>,
+    // セルの範囲を画像の不透明な部分だけに縮める
+    pub trim_alpha: bool,
+}
+
+// テクスチャアトラスのページの設定
+#[derive(Debug, Clone)]
+pub struct AtlasOptions {
+    // ページの幅と高さの上限(ピクセル)
+    pub max_size: u32,
+    // ページ数の上限、入りきらなければエラー
+    pub max_pages: usize,
+    // にじみ防止のセル同士の間隔(ピクセル)
+    pub padding: u32,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        AtlasOptions {
+            max_size: 2048,
+            max_pages: 8,
+            padding: 1,
+        }
+    }
+}
+
+// FPS変換時のキーフレームの時間の合わせ方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameMapping {
+    // 一番近いフレームに丸める
+    Nearest,
+    // キーの時間どおりに再現する
+    // 変換後のフレームに乗らないキーは、数値の属性なら毎フレームのキーに焼き込み、
+    // それ以外は時間を過ぎた最初のフレームに置く
+    Exact,
+}
+
+impl Default for FrameMapping {
+    fn default() -> Self {
+        FrameMapping::Nearest
+    }
+}
+
+impl std::str::FromStr for FrameMapping {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(FrameMapping::Nearest),
+            "exact" => Ok(FrameMapping::Exact),
+            _ => Err(failure::format_err!("unknown frame mapping: {}", s)),
+        }
+    }
+}
+
+// X/Y軸回転の出力方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationMode {
+    // 回転のままキーを出力する
+    Track,
+    // 2Dのみのランタイム向けに拡縮と反転のキーに焼き込む
+    Bake,
+}
+
+impl Default for RotationMode {
+    fn default() -> Self {
+        RotationMode::Track
+    }
+}
+
+impl std::str::FromStr for RotationMode {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "track" => Ok(RotationMode::Track),
+            "bake" => Ok(RotationMode::Bake),
+            _ => Err(failure::format_err!("unknown rotation mode: {}", s)),
+        }
+    }
+}
+
+// ユーザーデータを T::UserData に読み込む方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserDataMode {
+    // 文字列をJSONとして読む、数値・矩形・座標は使わない
+    Json,
+    // 設定されている値を常に次の形のオブジェクトにまとめて読む
+    // 文字列はJSONとして読まずにそのまま "string" に入れる
+    // { "integer": 1, "rect": { "x", "y", "width", "height" }, "point": { "x", "y" }, "string": "" }
+    Envelope,
+}
+
+impl Default for UserDataMode {
+    fn default() -> Self {
+        UserDataMode::Json
+    }
+}
+
+impl std::str::FromStr for UserDataMode {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(UserDataMode::Json),
+            "envelope" => Ok(UserDataMode::Envelope),
+            _ => Err(failure::format_err!("unknown user data mode: {}", s)),
+        }
+    }
+}
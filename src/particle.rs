@@ -0,0 +1,470 @@
+use crate::{
+    effect::{Effect, Emitter, ParticleBehavior},
+    error::Location,
+    report::Reporter,
+};
+use amethyst_sprite_studio::{
+    resource::animation,
+    types::{cell, interpolate::Interpolation},
+};
+
+// パーティクルを再生できない環境向けに、変換時にエフェクトを計算して
+// パーティクル1つにつき1パーツのキーフレームとして焼き込む
+// 乱数はエフェクトの種から作るので何度変換しても同じ結果になる
+// ただし乱数の作り方はこの変換器独自のもので、SpriteStudio の再生結果とは一致しない
+// 子エミッター、接線加速度、点重力、色の変化(アルファ以外)は焼き込まない
+
+// xorshift で乱数を作る
+struct Xorshift32(u32);
+
+impl Xorshift32 {
+    fn new(seed: u32) -> Self {
+        // 0 だと同じ値しか出ないので避ける
+        Xorshift32(if seed == 0 { 0x9e37_79b9 } else { seed })
+    }
+
+    fn next(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    fn range(&mut self, (min, max): (f32, f32)) -> f32 {
+        let rate = self.next() as f32 / std::u32::MAX as f32;
+        min + (max - min) * rate
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ParticleState {
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) rotation: f32,
+    pub(crate) scale_x: f32,
+    pub(crate) scale_y: f32,
+    pub(crate) alpha: f32,
+}
+
+struct Particle {
+    slot: usize,
+    age: usize,
+    lifespan: usize,
+    position: (f32, f32),
+    direction: (f32, f32),
+    speed: (f32, f32),
+    gravity_velocity: (f32, f32),
+    rotation: f32,
+    rotation_speed: f32,
+    rotation_factor: f32,
+    rotation_end_rate: f32,
+    scale: ((f32, f32), (f32, f32)),
+    alpha: (f32, f32),
+    fade: (f32, f32),
+}
+
+impl Particle {
+    fn life_rate(&self) -> f32 {
+        self.age as f32 / self.lifespan.max(1) as f32
+    }
+
+    fn state(&self) -> ParticleState {
+        let rate = self.life_rate();
+        let lerp = |(from, to): (f32, f32)| from + (to - from) * rate;
+        let ((sx0, sy0), (sx1, sy1)) = self.scale;
+
+        // フェードイン・フェードアウトは寿命に対する割合
+        let (fade_in, fade_out) = self.fade;
+        let fade = if fade_in > 0. && rate < fade_in {
+            rate / fade_in
+        } else if fade_out > 0. && rate > 1. - fade_out {
+            (1. - rate) / fade_out
+        } else {
+            1.
+        };
+
+        ParticleState {
+            x: self.position.0,
+            y: self.position.1,
+            rotation: self.rotation,
+            scale_x: lerp((sx0, sx1)),
+            scale_y: lerp((sy0, sy1)),
+            alpha: lerp(self.alpha) * fade,
+        }
+    }
+
+    fn step(&mut self, gravity: (f32, f32)) {
+        let rate = self.life_rate();
+        let speed = self.speed.0 + (self.speed.1 - self.speed.0) * rate;
+        self.gravity_velocity.0 += gravity.0;
+        self.gravity_velocity.1 += gravity.1;
+        self.position.0 += self.direction.0 * speed + self.gravity_velocity.0;
+        self.position.1 += self.direction.1 * speed + self.gravity_velocity.1;
+
+        // 回転速度は指定した寿命の割合までに factor 倍になる
+        let rotation_rate = if self.rotation_end_rate > 0. {
+            (rate / self.rotation_end_rate).min(1.)
+        } else {
+            1.
+        };
+        self.rotation += self.rotation_speed * (1. + (self.rotation_factor - 1.) * rotation_rate);
+        self.age += 1;
+    }
+}
+
+// 焼き込みに使うパーツ(スロット)ごとの発生元のエミッター
+// エミッターごとに同時に存在できる最大数のパーツを確保する
+pub(crate) fn slot_emitters(effect: &Effect) -> impl Iterator<Item = &Emitter> {
+    effect
+        .emitters
+        .iter()
+        .filter(|emitter| emitter.parent.is_none())
+        .flat_map(|emitter| std::iter::repeat(emitter).take(emitter.basic.max_particles))
+}
+
+pub(crate) fn slot_count(effect: &Effect) -> usize {
+    slot_emitters(effect).count()
+}
+
+pub(crate) fn slot_part_name(part_name: &str, slot: usize) -> String {
+    format!("{}_particle{:03}", part_name, slot)
+}
+
+// 焼き込むエフェクトパーツの (パーツID, エフェクト)
+// スロットのパーツはパックのパーツの後ろにこの順番で追加する
+pub(crate) fn baked_effect_parts<'a>(
+    pack: &sprite_studio::AnimationPack,
+    effects: &'a [Effect],
+) -> Vec<(usize, &'a Effect)> {
+    pack.parts()
+        .enumerate()
+        .filter_map(|(part_id, part)| {
+            let name = part.refference_effect()?;
+            let effect = effects.iter().find(|effect| effect.name == name)?;
+            Some((part_id, effect))
+        })
+        .collect()
+}
+
+// 焼き込まない子エミッターと効果を警告する
+// アニメーションごとではなく、エフェクトごとに一度だけ報告する
+pub(crate) fn warn_unbaked(effects: &[Effect], reporter: &mut Reporter) {
+    for effect in effects {
+        let location = Location::default().effect(&effect.name);
+        for emitter in &effect.emitters {
+            let location = location.emitter(&emitter.name);
+            if emitter.parent.is_some() {
+                reporter.warn(&location, "child emitter is not baked");
+                continue;
+            }
+            for behavior in &emitter.behaviors {
+                match behavior {
+                    ParticleBehavior::TangentialAcceleration(..)
+                    | ParticleBehavior::PointGravity { .. } => {
+                        reporter.warn(&location, format!("{:?} is not baked", behavior))
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+// エフェクトの時間 0 から frames までの各フレームのパーティクルの状態
+// 外側がフレーム、内側がパーツ(スロット)
+pub(crate) fn simulate(effect: &Effect, frames: usize) -> Vec<Vec<Option<ParticleState>>> {
+    let mut snapshots = vec![vec![None; slot_count(effect)]; frames];
+
+    let mut first_slot = 0;
+    for (idx, emitter) in effect.emitters.iter().enumerate() {
+        if emitter.parent.is_some() {
+            continue;
+        }
+        simulate_emitter(effect, idx, emitter, first_slot, &mut snapshots);
+        first_slot += emitter.basic.max_particles;
+    }
+    snapshots
+}
+
+fn simulate_emitter(
+    effect: &Effect,
+    emitter_index: usize,
+    emitter: &Emitter,
+    first_slot: usize,
+    snapshots: &mut [Vec<Option<ParticleState>>],
+) {
+    let mut seed = effect.seed.unwrap_or(0).wrapping_add(emitter_index as u32);
+    let mut delay = 0;
+    let mut gravity = (0., 0.);
+    for behavior in &emitter.behaviors {
+        match behavior {
+            &ParticleBehavior::OverwriteSeed(overwrite) => seed = overwrite,
+            &ParticleBehavior::Delay(frame) => delay = frame,
+            &ParticleBehavior::Gravity(x, y) => gravity = (x, y),
+            _ => {}
+        }
+    }
+    let mut rng = Xorshift32::new(seed);
+    let basic = &emitter.basic;
+    let slots = basic.max_particles;
+    let mut particles: Vec<Particle> = vec![];
+
+    for (frame, snapshot) in snapshots.iter_mut().enumerate() {
+        // 発生
+        let elapsed = frame.checked_sub(delay);
+        let emit = match elapsed {
+            Some(elapsed) => elapsed < basic.lifetime && elapsed % basic.interval.max(1) == 0,
+            None => false,
+        };
+        if emit {
+            for _ in 0..basic.create_count {
+                let slot = match (0..slots).find(|slot| particles.iter().all(|p| p.slot != *slot)) {
+                    Some(slot) => slot,
+                    None => break,
+                };
+                particles.push(spawn(&mut rng, emitter, slot));
+            }
+        }
+
+        for particle in &particles {
+            snapshot[first_slot + particle.slot] = Some(particle.state());
+        }
+
+        for particle in &mut particles {
+            particle.step(gravity);
+        }
+        particles.retain(|particle| particle.age < particle.lifespan);
+    }
+}
+
+fn spawn(rng: &mut Xorshift32, emitter: &Emitter, slot: usize) -> Particle {
+    let basic = &emitter.basic;
+    let half_variance = basic.angle_variance / 2.;
+    let angle = rng
+        .range((basic.angle - half_variance, basic.angle + half_variance))
+        .to_radians();
+    let speed = rng.range(basic.speed);
+
+    let mut particle = Particle {
+        slot,
+        age: 0,
+        lifespan: rng.range(basic.lifespan).max(1.) as usize,
+        position: (0., 0.),
+        direction: (angle.cos(), angle.sin()),
+        speed: (speed, speed),
+        gravity_velocity: (0., 0.),
+        rotation: 0.,
+        rotation_speed: 0.,
+        rotation_factor: 1.,
+        rotation_end_rate: 0.,
+        scale: ((1., 1.), (1., 1.)),
+        alpha: (1., 1.),
+        fade: (0., 0.),
+    };
+
+    let mut end_scale = None;
+    let mut end_alpha = None;
+    for behavior in &emitter.behaviors {
+        match behavior {
+            &ParticleBehavior::InitPosition { x, y } => {
+                particle.position = (rng.range(x), rng.range(y))
+            }
+            &ParticleBehavior::InitRotation { rotation, speed } => {
+                particle.rotation = rng.range(rotation);
+                particle.rotation_speed = rng.range(speed);
+            }
+            &ParticleBehavior::TransRotation {
+                factor,
+                end_life_rate,
+            } => {
+                particle.rotation_factor = factor;
+                particle.rotation_end_rate = end_life_rate;
+            }
+            &ParticleBehavior::TransSpeed(end_speed) => particle.speed.1 = rng.range(end_speed),
+            &ParticleBehavior::InitSize { x, y, scale } => {
+                let scale = rng.range(scale);
+                particle.scale.0 = (rng.range(x) * scale, rng.range(y) * scale);
+            }
+            &ParticleBehavior::TransSize { x, y, scale } => {
+                let scale = rng.range(scale);
+                end_scale = Some((rng.range(x) * scale, rng.range(y) * scale));
+            }
+            &ParticleBehavior::InitColor(min, max) => {
+                particle.alpha.0 = rng.range((min[3], max[3]));
+            }
+            &ParticleBehavior::TransColor(min, max) => {
+                end_alpha = Some(rng.range((min[3], max[3])));
+            }
+            &ParticleBehavior::AlphaFade(fade_in, fade_out) => particle.fade = (fade_in, fade_out),
+            _ => {}
+        }
+    }
+    // 変化の指定がなければ発生時の値のまま
+    particle.scale.1 = end_scale.unwrap_or(particle.scale.0);
+    particle.alpha.1 = end_alpha.unwrap_or(particle.alpha.0);
+    particle
+}
+
+// エフェクトキーの再生開始位置と速度
+pub(crate) struct EffectPlayKey {
+    pub(crate) frame: usize,
+    pub(crate) start_time: f32,
+    pub(crate) speed: f32,
+}
+
+// アニメーションの各フレームでのエフェクトの時間から、
+// 焼き込んだパーティクルの状態をスロットのパーツのキーにする
+// エフェクトは自分のFPSで進むので、アニメーションのFPSとの比で時間を合わせる
+pub(crate) fn bake_effect<U>(
+    builder: &mut animation::AnimationBuilder<U>,
+    first_part_id: usize,
+    effect: &Effect,
+    keys: &[EffectPlayKey],
+    frame_count: usize,
+    animation_fps: u32,
+) {
+    let rate = effect.fps as f32 / animation_fps as f32;
+    let effect_frame = |frame: usize| {
+        keys.iter().rev().find(|key| key.frame <= frame).map(|key| {
+            ((frame - key.frame) as f32 * rate * key.speed + key.start_time).max(0.) as usize
+        })
+    };
+    let length = (0..frame_count)
+        .filter_map(effect_frame)
+        .max()
+        .map(|last| last + 1)
+        .unwrap_or(0);
+    let snapshots = simulate(effect, length);
+
+    // スロットごとに使うセルは発生元のエミッターで決まる
+    let slot_cells = slot_emitters(effect)
+        .map(|emitter| emitter.cell)
+        .collect::<Vec<_>>();
+    for (slot, cell) in slot_cells.iter().enumerate() {
+        if let &Some((map_id, cell_id)) = cell {
+            builder.add_cell(
+                first_part_id + slot,
+                0,
+                Interpolation::Step,
+                cell::CellBuilder::new(map_id, cell_id).build(),
+            );
+        }
+    }
+
+    let (layout_x, layout_y) = effect.layout_scale;
+    let mut prev_hide = vec![None; slot_cells.len()];
+    for frame in 0..frame_count {
+        let states = effect_frame(frame).and_then(|index| snapshots.get(index));
+        for (slot, prev_hide) in prev_hide.iter_mut().enumerate() {
+            let part_id = first_part_id + slot;
+            // セルのないエミッターは表示できない
+            let state = match slot_cells[slot] {
+                Some(_) => states.and_then(|states| states[slot]),
+                None => None,
+            };
+            if let Some(state) = state {
+                builder.add_pos_x(part_id, frame, Interpolation::Linear, state.x * layout_x);
+                builder.add_pos_y(part_id, frame, Interpolation::Linear, state.y * layout_y);
+                builder.add_rotated(part_id, frame, Interpolation::Linear, state.rotation);
+                builder.add_scale_x(part_id, frame, Interpolation::Linear, state.scale_x);
+                builder.add_scale_y(part_id, frame, Interpolation::Linear, state.scale_y);
+                builder.add_alpha(part_id, frame, Interpolation::Linear, state.alpha);
+            }
+            // 生きていない間は非表示
+            let hide = state.is_none();
+            if *prev_hide != Some(hide) {
+                builder.add_hide(part_id, frame, Interpolation::Step, hide);
+                *prev_hide = Some(hide);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effect::{EffectBlend, EmitterBasic};
+
+    fn effect(seed: u32, behaviors: Vec<ParticleBehavior>) -> Effect {
+        Effect {
+            name: "effect".into(),
+            fps: 60,
+            seed: Some(seed),
+            layout_scale: (1., 1.),
+            emitters: vec![Emitter {
+                name: "emitter".into(),
+                parent: None,
+                cell: Some((0, 0)),
+                blend: EffectBlend::Add,
+                basic: EmitterBasic {
+                    priority: 64.,
+                    max_particles: 8,
+                    create_count: 2,
+                    interval: 3,
+                    lifetime: 30,
+                    speed: (1., 5.),
+                    lifespan: (10., 20.),
+                    angle: 0.,
+                    angle_variance: 360.,
+                },
+                behaviors,
+            }],
+        }
+    }
+
+    fn init_behaviors() -> Vec<ParticleBehavior> {
+        vec![
+            ParticleBehavior::InitPosition {
+                x: (-10., 10.),
+                y: (-10., 10.),
+            },
+            ParticleBehavior::InitSize {
+                x: (0.5, 1.5),
+                y: (0.5, 1.5),
+                scale: (1., 2.),
+            },
+        ]
+    }
+
+    #[test]
+    fn same_seed_gives_same_particles() {
+        let effect = effect(1234, init_behaviors());
+        let first = simulate(&effect, 40);
+        assert!(first.iter().flatten().any(Option::is_some));
+        assert_eq!(first, simulate(&effect, 40));
+
+        let other = simulate(&self::effect(4321, init_behaviors()), 40);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn overwrite_seed_replaces_effect_seed() {
+        let mut behaviors = init_behaviors();
+        behaviors.push(ParticleBehavior::OverwriteSeed(99));
+        let first = simulate(&effect(1234, behaviors.clone()), 40);
+        let second = simulate(&effect(4321, behaviors), 40);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn unbaked_behaviors_are_reported() {
+        let mut behaviors = init_behaviors();
+        behaviors.push(ParticleBehavior::TangentialAcceleration((1., 2.)));
+        behaviors.push(ParticleBehavior::PointGravity {
+            position: (0., 0.),
+            power: 1.,
+        });
+        let mut baked = effect(1234, behaviors);
+        let mut child = effect(1234, vec![]).emitters.remove(0);
+        child.parent = Some(0);
+        baked.emitters.push(child);
+
+        let mut reporter = Reporter::collect_all();
+        warn_unbaked(&[baked], &mut reporter);
+        let report = reporter.into_report();
+        assert_eq!(report.warnings.len(), 3);
+        assert!(report.errors.is_empty());
+    }
+}
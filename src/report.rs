@@ -0,0 +1,107 @@
+use crate::error::{ConvertError, Location, ParseAnimationError};
+use std::collections::BTreeMap;
+
+#[derive(Debug)]
+pub struct Warning {
+    pub location: Location,
+    pub message: String,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}\n    at {}", self.message, self.location)
+    }
+}
+
+// 変換中に見つかったエラーと警告
+#[derive(Debug, Default)]
+pub struct Report {
+    pub errors: Vec<ConvertError>,
+    pub warnings: Vec<Warning>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    // エラーの種類ごとの件数
+    pub fn summary(&self) -> BTreeMap<&'static str, usize> {
+        let mut summary = BTreeMap::new();
+        for error in &self.errors {
+            let kind = error
+                .error
+                .downcast_ref::<ParseAnimationError>()
+                .map(|err| err.kind())
+                .unwrap_or("Other");
+            *summary.entry(kind).or_insert(0) += 1;
+        }
+        summary
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for error in &self.errors {
+            writeln!(f, "error: {}", error)?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "warning: {}", warning)?;
+        }
+        for (kind, count) in self.summary() {
+            writeln!(f, "{}: {}", kind, count)?;
+        }
+        write!(
+            f,
+            "{} errors, {} warnings",
+            self.errors.len(),
+            self.warnings.len()
+        )
+    }
+}
+
+// エラーを最初の1件で止めるか、全部集めるかを切り替える
+pub(crate) struct Reporter {
+    collect_all: bool,
+    report: Report,
+}
+
+impl Reporter {
+    pub(crate) fn fail_fast() -> Self {
+        Reporter {
+            collect_all: false,
+            report: Report::default(),
+        }
+    }
+
+    pub(crate) fn collect_all() -> Self {
+        Reporter {
+            collect_all: true,
+            report: Report::default(),
+        }
+    }
+
+    // 収集モードならエラーを記録して変換を続ける
+    pub(crate) fn error(&mut self, error: ConvertError) -> Result<(), ConvertError> {
+        if self.collect_all {
+            log::error!("{}", error);
+            self.report.errors.push(error);
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+
+    pub(crate) fn warn<S: Into<String>>(&mut self, location: &Location, message: S) {
+        let warning = Warning {
+            location: location.clone(),
+            message: message.into(),
+        };
+        log::warn!("{}", warning);
+        self.report.warnings.push(warning);
+    }
+
+    pub(crate) fn into_report(self) -> Report {
+        self.report
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{
+    convert::FrameTiming,
+    curve::{self, CurveKey},
+};
+use amethyst_sprite_studio::{resource::animation, types::interpolate::Interpolation};
+use sprite_studio::AttributeTag;
+
+// 2Dのみのランタイム向けにX/Y軸回転を拡縮と反転に焼き込む
+// Y軸回転は横幅、X軸回転は縦幅の見た目の変化になる
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+impl Axis {
+    pub(crate) fn from_tag(tag: &AttributeTag) -> Option<Self> {
+        match tag {
+            AttributeTag::Roty => Some(Axis::Horizontal),
+            AttributeTag::Rotx => Some(Axis::Vertical),
+            _ => None,
+        }
+    }
+
+    // 焼き込み先と同じ属性にキーがあると上書きしてしまう
+    pub(crate) fn conflicts_with(&self, tag: &AttributeTag) -> bool {
+        match self {
+            Axis::Horizontal => match tag {
+                AttributeTag::Sclx | AttributeTag::Fliph => true,
+                _ => false,
+            },
+            Axis::Vertical => match tag {
+                AttributeTag::Scly | AttributeTag::Flipv => true,
+                _ => false,
+            },
+        }
+    }
+}
+
+// 回転は拡縮に対して線形ではないので、キーの間も毎フレーム焼き込む
+// 角度は元のFPSのままキーの補間どおりにカーブを評価する
+pub(crate) fn bake_rotation<U>(
+    builder: &mut animation::AnimationBuilder<U>,
+    part_id: usize,
+    axis: Axis,
+    keys: &[CurveKey],
+    timing: &FrameTiming,
+) {
+    let (first, last) = match (keys.first(), keys.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return,
+    };
+    let mut prev_flip = None;
+    for frame in timing.frame(first.time as usize)..=timing.frame(last.time as usize) {
+        let angle = curve::sample(keys, timing.source_time(frame));
+        let cos = angle.to_radians().cos();
+        let scale = cos.abs();
+        // 裏返ったら反転させる
+        let flip = cos < 0.;
+
+        match axis {
+            Axis::Horizontal => builder.add_scale_x(part_id, frame, Interpolation::Linear, scale),
+            Axis::Vertical => builder.add_scale_y(part_id, frame, Interpolation::Linear, scale),
+        }
+        if prev_flip != Some(flip) {
+            match axis {
+                Axis::Horizontal => builder.add_flip_h(part_id, frame, Interpolation::Step, flip),
+                Axis::Vertical => builder.add_flip_v(part_id, frame, Interpolation::Step, flip),
+            }
+            prev_flip = Some(flip);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use crate::{
+    convert::convert_float,
+    error::{ConvertError, Location, ParseAnimationError},
+    report::Reporter,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// パックごとのボーン階層とメッシュのウェイト
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Skeleton {
+    pub bones: Vec<Bone>,
+    pub bindings: Vec<MeshBinding>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Bone {
+    pub part_id: usize,
+    pub name: String,
+    pub kind: BoneKind,
+    // 一番近い祖先のボーンの bones 内のインデックス
+    pub parent: Option<usize>,
+    // 親ボーンからの相対、間にボーン以外のパーツがあればその変形も含む
+    pub bind_pose: BindPose,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum BoneKind {
+    Bone,
+    // IK などで動かす関節
+    Joint,
+    // ボーン階層のまとめ役
+    Armature,
+}
+
+// Setup アニメーションの先頭キーから作る初期姿勢
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BindPose {
+    pub position: (f32, f32),
+    pub rotation: f32,
+    pub scale: (f32, f32),
+}
+
+impl Default for BindPose {
+    fn default() -> Self {
+        BindPose {
+            position: (0., 0.),
+            rotation: 0.,
+            scale: (1., 1.),
+        }
+    }
+}
+
+impl BindPose {
+    // この姿勢の中に child を置いたときの、この姿勢の親からの姿勢
+    fn compose(&self, child: &BindPose) -> BindPose {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let (x, y) = (
+            child.position.0 * self.scale.0,
+            child.position.1 * self.scale.1,
+        );
+        BindPose {
+            position: (
+                self.position.0 + x * cos - y * sin,
+                self.position.1 + x * sin + y * cos,
+            ),
+            rotation: self.rotation + child.rotation,
+            scale: (self.scale.0 * child.scale.0, self.scale.1 * child.scale.1),
+        }
+    }
+}
+
+// メッシュパーツの頂点ごとのボーンの影響
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MeshBinding {
+    pub part_id: usize,
+    pub vertices: Vec<Vec<BoneWeight>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BoneWeight {
+    // bones 内のインデックス
+    pub bone: usize,
+    pub weight: f32,
+    // ボーンから見た頂点の位置
+    pub offset: (f32, f32),
+}
+
+// ボーン・関節・アーマチュアのあるパックだけ出力する
+// 収集モードではエラーのあったパックを飛ばして続ける
+pub(crate) fn make_skeletons(
+    project: &sprite_studio::SpriteStudioData,
+    reporter: &mut Reporter,
+) -> Result<BTreeMap<String, Skeleton>, ConvertError> {
+    let mut skeletons = BTreeMap::new();
+    for pack in project.packs() {
+        match make_skeleton(pack) {
+            Ok(Some(skeleton)) => {
+                skeletons.insert(pack.name().to_string(), skeleton);
+            }
+            Ok(None) => {}
+            Err(err) => reporter.error(Location::default().pack(pack.name()).error(err))?,
+        }
+    }
+    Ok(skeletons)
+}
+
+fn make_skeleton(
+    pack: &sprite_studio::AnimationPack,
+) -> Result<Option<Skeleton>, ParseAnimationError> {
+    let bone_parts = pack
+        .parts()
+        .filter_map(|part| bone_kind(part).map(|kind| (part, kind)))
+        .collect::<Vec<_>>();
+    if bone_parts.is_empty() {
+        return Ok(None);
+    }
+
+    let setup = pack
+        .animations()
+        .find(|animation| animation.name() == "Setup");
+
+    let local_pose = |name: &str| match setup {
+        Some(setup) => make_bind_pose(setup, name),
+        None => Ok(BindPose::default()),
+    };
+
+    let mut bones = vec![];
+    for (part, kind) in &bone_parts {
+        // 親をたどって一番近いボーンを探す
+        // 間のヌルパーツなどの変形は合成してボーンからの相対にする
+        // ボーンがなければルートで、姿勢はパックの原点からになる
+        let mut bind_pose = local_pose(part.name())?;
+        let mut parent_index = part.parent();
+        let parent = loop {
+            if let Some(parent) = bone_parts
+                .iter()
+                .position(|(parent, _)| parent.index() == parent_index)
+            {
+                break Some(parent);
+            }
+            match pack.parts().find(|parent| parent.index() == parent_index) {
+                Some(parent) => {
+                    bind_pose = local_pose(parent.name())?.compose(&bind_pose);
+                    parent_index = parent.parent();
+                }
+                None => break None,
+            }
+        };
+        bones.push(Bone {
+            part_id: part.index() as usize,
+            name: part.name().into(),
+            kind: *kind,
+            parent,
+            bind_pose,
+        });
+    }
+
+    let mut bindings = vec![];
+    for bind in pack.mesh_binds() {
+        let mut vertices = vec![];
+        for vertex in bind.vertices() {
+            let mut weights = vec![];
+            for (bone_index, weight, offset) in vertex.bones() {
+                let bone = bones
+                    .iter()
+                    .position(|bone| bone.part_id as i32 == bone_index)
+                    .ok_or(ParseAnimationError::UnknownBone { index: bone_index })?;
+                weights.push(BoneWeight {
+                    bone,
+                    weight,
+                    offset,
+                });
+            }
+            vertices.push(weights);
+        }
+        bindings.push(MeshBinding {
+            part_id: bind.part_index() as usize,
+            vertices,
+        });
+    }
+
+    Ok(Some(Skeleton { bones, bindings }))
+}
+
+fn bone_kind(part: &sprite_studio::Part) -> Option<BoneKind> {
+    match part.part_type() {
+        sprite_studio::PartType::Bone => Some(BoneKind::Bone),
+        sprite_studio::PartType::Joint => Some(BoneKind::Joint),
+        sprite_studio::PartType::Armature => Some(BoneKind::Armature),
+        _ => None,
+    }
+}
+
+fn make_bind_pose(
+    setup: &sprite_studio::Animation,
+    part_name: &str,
+) -> Result<BindPose, ParseAnimationError> {
+    let mut pose = BindPose::default();
+    let part_anim = match setup.part_animes().find(|pa| pa.name() == part_name) {
+        Some(part_anim) => part_anim,
+        None => return Ok(pose),
+    };
+
+    for attr in part_anim.attributes() {
+        let key = match attr.keys().next() {
+            Some(key) => key,
+            None => continue,
+        };
+        match attr.tag() {
+            sprite_studio::AttributeTag::Posx => pose.position.0 = convert_float(key)?,
+            sprite_studio::AttributeTag::Posy => pose.position.1 = convert_float(key)?,
+            sprite_studio::AttributeTag::Rotz => pose.rotation = convert_float(key)?,
+            sprite_studio::AttributeTag::Sclx => pose.scale.0 = convert_float(key)?,
+            sprite_studio::AttributeTag::Scly => pose.scale.1 = convert_float(key)?,
+            _ => {}
+        }
+    }
+    Ok(pose)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compose_through_rotated_and_scaled_parent() {
+        let parent = BindPose {
+            position: (10., 0.),
+            rotation: 90.,
+            scale: (2., 1.),
+        };
+        let child = BindPose {
+            position: (5., 0.),
+            rotation: 30.,
+            scale: (0.5, 3.),
+        };
+        let pose = parent.compose(&child);
+        assert!((pose.position.0 - 10.).abs() < 1e-4);
+        assert!((pose.position.1 - 10.).abs() < 1e-4);
+        assert_eq!(pose.rotation, 120.);
+        assert_eq!(pose.scale, (1., 3.));
+    }
+}
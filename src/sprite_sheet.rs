@@ -1,27 +1,13 @@
+use crate::trim::CellRect;
 use amethyst::renderer::sprite::{SpriteList, SpritePosition, Sprites};
 
 use sprite_studio::AnimationCells;
 
-pub(crate) fn make_sprite_sheet(cell_map: &AnimationCells) -> Sprites {
+// rects はセルごとのテクスチャ上の範囲
+pub(crate) fn make_sprite_sheet(cell_map: &AnimationCells, rects: &[CellRect]) -> Sprites {
     let mut sprites = vec![];
-    for cell in cell_map.cells() {
-        let (x, y) = cell.position();
-        let (x, y) = (x as u32, y as u32);
-        let (width, height) = cell.size();
-        let (width, height) = (width as u32, height as u32);
-        let (pivot_x, pivot_y) = cell.pivot();
-        let (pivot_x, pivot_y) = (pivot_x * cell.size().0, pivot_y * cell.size().1);
-        let flip_horizontal = false;
-        let flip_vertical = false;
-        let sprite = SpritePosition {
-            x,
-            y,
-            width,
-            height,
-            flip_horizontal,
-            flip_vertical,
-            offsets: Some([pivot_x, pivot_y]),
-        };
+    for (cell, rect) in cell_map.cells().zip(rects) {
+        let sprite = make_sprite_position(cell, rect, (rect.x, rect.y));
         sprites.push(sprite);
     }
 
@@ -35,3 +21,73 @@ pub(crate) fn make_sprite_sheet(cell_map: &AnimationCells) -> Sprites {
 
     Sprites::List(sprite)
 }
+
+// テクスチャ上の位置だけ差し替えられるように分けておく
+// 範囲を切り詰めたセルはピボットの見た目の位置が変わらないようにオフセットをずらす
+pub(crate) fn make_sprite_position(
+    cell: &sprite_studio::Cell,
+    rect: &CellRect,
+    (x, y): (u32, u32),
+) -> SpritePosition {
+    let offsets = pivot_offsets(cell.position(), cell.size(), cell.pivot(), rect);
+
+    let flip_horizontal = false;
+    let flip_vertical = false;
+    SpritePosition {
+        x,
+        y,
+        width: rect.width,
+        height: rect.height,
+        flip_horizontal,
+        flip_vertical,
+        offsets: Some(offsets),
+    }
+}
+
+// ピボットはセルの中心からの比率
+fn pivot_offsets(
+    (cell_x, cell_y): (f32, f32),
+    (cell_width, cell_height): (f32, f32),
+    (pivot_x, pivot_y): (f32, f32),
+    rect: &CellRect,
+) -> [f32; 2] {
+    let (pivot_x, pivot_y) = (pivot_x * cell_width, pivot_y * cell_height);
+
+    // 元のセルの中心から切り詰めた範囲の中心までのずれ
+    // 画像は下向き、オフセットは上向きが正
+    let shift_x = (rect.x as f32 + rect.width as f32 / 2.) - (cell_x + cell_width / 2.);
+    let shift_y = (rect.y as f32 + rect.height as f32 / 2.) - (cell_y + cell_height / 2.);
+
+    [pivot_x - shift_x, pivot_y + shift_y]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: u32, y: u32, width: u32, height: u32) -> CellRect {
+        CellRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn untrimmed_cell_keeps_pivot() {
+        let offsets = pivot_offsets((10., 20.), (40., 60.), (0.25, -0.5), &rect(10, 20, 40, 60));
+        assert_eq!(offsets, [10., -30.]);
+    }
+
+    #[test]
+    fn trimmed_cell_shifts_pivot_to_same_place() {
+        // 100x100 のセルから (55, 40) の 30x40 だけ残す
+        // 残した範囲の中心は元の中心から右に 20、下に 10 ずれる
+        let offsets = pivot_offsets((0., 0.), (100., 100.), (0., 0.), &rect(55, 40, 30, 40));
+        assert_eq!(offsets, [-20., 10.]);
+
+        let offsets = pivot_offsets((0., 0.), (100., 100.), (0.5, 0.5), &rect(55, 40, 30, 40));
+        assert_eq!(offsets, [30., 60.]);
+    }
+}
@@ -0,0 +1,13 @@
+use amethyst_sprite_studio::traits::animation_file::AnimationFile;
+use serde::{Deserialize, Serialize};
+
+// パック名とアニメーション名をそのまま文字列キーとして扱う
+// ユーザーデータは任意のJSONを受け付ける
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StringKeyFile;
+
+impl AnimationFile for StringKeyFile {
+    type UserData = serde_json::Value;
+    type PackKey = String;
+    type AnimationKey = String;
+}
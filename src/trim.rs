@@ -0,0 +1,180 @@
+use crate::{cell_map::CellMapTable, convert};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use sprite_studio::AttributeTag;
+
+// セルのテクスチャ上の範囲(ピクセル)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CellRect {
+    pub(crate) x: u32,
+    pub(crate) y: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+impl CellRect {
+    fn of_cell(cell: &sprite_studio::Cell) -> Self {
+        let (x, y) = cell.position();
+        let (width, height) = cell.size();
+        CellRect {
+            x: x as u32,
+            y: y as u32,
+            width: width as u32,
+            height: height as u32,
+        }
+    }
+
+    fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+}
+
+// 透明な余白を切り詰めて減ったセルの面積(ピクセル数)
+// アトラスにまとめない場合は画像をそのままコピーするので、テクスチャは小さくならない
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TrimSummary {
+    pub trimmed_cells: usize,
+    // サイズ・頂点・ピボット・UVのキーで変形するため切り詰めなかったセル
+    pub kept_cells: usize,
+    pub original_area: u64,
+    pub trimmed_area: u64,
+}
+
+impl TrimSummary {
+    pub fn saved_area(&self) -> u64 {
+        self.original_area - self.trimmed_area
+    }
+}
+
+// セルマップのセル範囲そのまま
+// 並びは [全体のセルマップID][セルID]
+pub(crate) fn cell_rects(project: &sprite_studio::SpriteStudioData) -> Vec<Vec<CellRect>> {
+    project
+        .cell_maps()
+        .map(|cell_map| cell_map.cells().map(CellRect::of_cell).collect())
+        .collect()
+}
+
+// セルの範囲を画像の不透明な部分だけに縮める
+// images は project.cell_maps() の順番
+pub(crate) fn trim_cell_rects(
+    project: &sprite_studio::SpriteStudioData,
+    images: &[RgbaImage],
+) -> (Vec<Vec<CellRect>>, TrimSummary) {
+    let mut summary = TrimSummary::default();
+    let deformed = deformed_cells(project);
+    let rects = project
+        .cell_maps()
+        .zip(images)
+        .zip(deformed)
+        .map(|((cell_map, image), deformed)| {
+            cell_map
+                .cells()
+                .zip(deformed)
+                .map(|(cell, deformed)| {
+                    let rect = CellRect::of_cell(cell);
+                    // メッシュの頂点はセルの左上が原点なので縮めない
+                    // 全部透明なセルも元の範囲のまま
+                    let trimmed = match cell.mesh() {
+                        Some(_) => rect,
+                        None if deformed => {
+                            summary.kept_cells += 1;
+                            rect
+                        }
+                        None => opaque_bounds(image, rect).unwrap_or(rect),
+                    };
+                    summary.original_area += rect.area();
+                    summary.trimmed_area += trimmed.area();
+                    if trimmed != rect {
+                        summary.trimmed_cells += 1;
+                    }
+                    trimmed
+                })
+                .collect()
+        })
+        .collect();
+    (rects, summary)
+}
+
+// サイズ・頂点・ピボット・UVのキーは元のセルの範囲が基準なので、
+// それらのキーがあるパーツに使われるセルを [全体のセルマップID][セルID] で印をつける
+fn deformed_cells(project: &sprite_studio::SpriteStudioData) -> Vec<Vec<bool>> {
+    let table = CellMapTable::new(project);
+    let mut deformed = project
+        .cell_maps()
+        .map(|cell_map| vec![false; cell_map.cells().count()])
+        .collect::<Vec<_>>();
+
+    for pack in project.packs() {
+        // 解決できないセルは変換時にエラーになるのでここでは無視する
+        let cell_maps = match table.pack_cell_maps(pack) {
+            Ok(cell_maps) => cell_maps,
+            Err(_) => continue,
+        };
+        // 別のアニメーションで変形していても同じパーツなら切り詰めない
+        let deformed_parts = pack
+            .animations()
+            .flat_map(|animation| animation.part_animes())
+            .filter(|part_anim| part_anim.attributes().any(|attr| deforms_cell(attr.tag())))
+            .map(|part_anim| part_anim.name().to_string())
+            .collect::<Vec<_>>();
+
+        let cell_keys = pack
+            .animations()
+            .flat_map(|animation| animation.part_animes())
+            .filter(|part_anim| deformed_parts.iter().any(|name| name == part_anim.name()))
+            .flat_map(|part_anim| part_anim.attributes())
+            .filter(|attr| match attr.tag() {
+                AttributeTag::Cell => true,
+                _ => false,
+            })
+            .flat_map(|attr| attr.keys());
+        for key in cell_keys {
+            if let Ok((map_id, cell_id)) = convert::cell_key_ids(key, &cell_maps) {
+                deformed[map_id][cell_id] = true;
+            }
+        }
+    }
+    deformed
+}
+
+fn deforms_cell(tag: &AttributeTag) -> bool {
+    match tag {
+        AttributeTag::Sizx
+        | AttributeTag::Sizy
+        | AttributeTag::Vertex
+        | AttributeTag::Pvtx
+        | AttributeTag::Pvty
+        | AttributeTag::Uvtx
+        | AttributeTag::Uvty
+        | AttributeTag::Uvrz
+        | AttributeTag::Uvsx
+        | AttributeTag::Uvsy => true,
+        _ => false,
+    }
+}
+
+fn opaque_bounds(image: &RgbaImage, rect: CellRect) -> Option<CellRect> {
+    let right = (rect.x + rect.width).min(image.width());
+    let bottom = (rect.y + rect.height).min(image.height());
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for y in rect.y..bottom {
+        for x in rect.x..right {
+            if image.get_pixel(x, y)[3] == 0 {
+                continue;
+            }
+            bounds = Some(match bounds {
+                Some((left, top, right, bottom)) => {
+                    (left.min(x), top.min(y), right.max(x), bottom.max(y))
+                }
+                None => (x, y, x, y),
+            });
+        }
+    }
+    bounds.map(|(left, top, right, bottom)| CellRect {
+        x: left,
+        y: top,
+        width: right - left + 1,
+        height: bottom - top + 1,
+    })
+}